@@ -0,0 +1,119 @@
+//! Benchmark of the recalc pass with the recalc cache on and off, measuring text
+//! with `FontFileMeasurer`: `cargo bench --bench recalc`.
+
+use std::time::{Duration, Instant};
+use exgui_renderer_nanovg::{NanovgRenderer, FontFileMeasurer, RenderStats};
+use exgui::{
+    egml, Component, ChangeView, Node, Comp, Color, Drawable, Shape, AlignHor::*, AlignVer::*,
+    PathCommand::*, Transform,
+};
+
+const COLS: usize = 40;
+const ROWS: usize = 50;
+const WARMUP_FRAMES: u32 = 10;
+const FRAMES: u32 = 200;
+
+#[derive(Debug, Default)]
+struct Model;
+
+impl Component for Model {
+    type Message = ();
+    type Properties = ();
+
+    fn create(_props: &Self::Properties) -> Self {
+        Model
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ChangeView {
+        ChangeView::None
+    }
+
+    fn view(&self) -> Node<Self> {
+        egml! {
+            <group>
+                { for (0..COLS * ROWS).map(|n| self.view_cell(n)) }
+
+                <path cmd = vec![Move([480.0, 400.0]), Line([480.0, 100.0]), Close],
+                    stroke = (Color::Red, 3),
+                    transform = Transform::new(), />
+            </group>
+        }
+    }
+}
+
+impl Model {
+    fn view_cell(&self, n: usize) -> Node<Model> {
+        let (col, row) = ((n % COLS) as f32, (n / COLS) as f32);
+        egml! {
+            <rect stroke = (Color::Blue, 1), >
+                <text x = col * 24.0, y = row * 16.0, font_name = "Roboto", font_size = 10,
+                        align = (Left, Top), fill = Color::White, >
+                    { format!("{}", n) }
+                </text>
+            </rect>
+        }
+    }
+}
+
+fn node_mut<'a>(draw: &'a mut dyn Drawable, path: &[usize]) -> Option<&'a mut dyn Drawable> {
+    match path.split_first() {
+        Some((&idx, rest)) => node_mut(draw.childs_mut()?.into_iter().nth(idx)?, rest),
+        None => Some(draw),
+    }
+}
+
+/// Rotates the hand path, the only subtree that changes between frames.
+fn rotate_hand(comp: &mut Comp, hand_path: &[usize]) {
+    let hand = node_mut(comp, hand_path).expect("Hand path must exist");
+    if let Some(Shape::Path(p)) = hand.shape_mut() {
+        if let Some(transform) = p.transform.as_mut() {
+            transform.rotate(0.01);
+        }
+    }
+}
+
+fn run(measurer: &FontFileMeasurer, use_cache: bool, animate: bool) -> (Duration, RenderStats) {
+    let renderer = NanovgRenderer::default().with_width(960.0).with_height(800.0);
+    let renderer = if use_cache { renderer.with_recalc_cache() } else { renderer };
+
+    let mut comp = Comp::new::<Model>(());
+    comp.resolve(None);
+    let hand_path = NanovgRenderer::find_path(&comp, |shape| matches!(shape, Shape::Path(_)))
+        .expect("Hand path must exist");
+
+    for _ in 0..WARMUP_FRAMES {
+        renderer.recalc_with(measurer, &mut comp);
+    }
+
+    let mut elapsed = Duration::default();
+    let mut stats = RenderStats::default();
+    for _ in 0..FRAMES {
+        if animate {
+            rotate_hand(&mut comp, &hand_path);
+        }
+        let start = Instant::now();
+        stats = renderer.recalc_with(measurer, &mut comp);
+        elapsed += start.elapsed();
+    }
+    (elapsed / FRAMES, stats)
+}
+
+fn main() {
+    let mut measurer = FontFileMeasurer::new();
+    measurer.load_font("Roboto", "resources/Roboto-Regular.ttf").expect("Roboto must be loadable");
+
+    for &(scenario, animate) in [("static tree", false), ("one changed subtree", true)].iter() {
+        for &use_cache in [false, true].iter() {
+            let (frame_time, stats) = run(&measurer, use_cache, animate);
+            println!(
+                "{}, recalc cache {}: {:.3} ms per frame, {} recalculated, {} cached, {} measurements",
+                scenario,
+                if use_cache { "on" } else { "off" },
+                frame_time.as_secs() as f64 * 1e3 + f64::from(frame_time.subsec_nanos()) / 1e6,
+                stats.recalculated_nodes,
+                stats.cached_nodes,
+                stats.text_measurements,
+            );
+        }
+    }
+}
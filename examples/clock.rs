@@ -298,10 +298,11 @@ fn main() {
     comp.resolve(None);
 
     app.run_proc(&mut comp, |app, clock| {
-        clock.send_self(Msg::ResizeWindow(app.dimensions()));
-
         let (dims, hdpi) = (app.dimensions(), app.window().hidpi_factor());
-        app.renderer_mut().set_dimensions(dims, hdpi);
+        app.renderer_mut().set_physical_dimensions(dims, hdpi);
+
+        let (width, height) = app.renderer_mut().logical_dimensions();
+        clock.send_self(Msg::ResizeWindow((width as u32, height as u32)));
         AppState::Continue
     }).unwrap();
 }
\ No newline at end of file
@@ -0,0 +1,134 @@
+use std::env;
+use std::time::{Duration, Instant};
+use exgui_renderer_nanovg::NanovgRenderer;
+use exgui_controller_glutin::{App, AppState, glutin};
+use exgui::{
+    egml, Component, ChangeView, Node, Comp, Color, AlignHor::*, AlignVer::*,
+    PathCommand::*, Transform, SystemMessage
+};
+
+const COLS: usize = 40;
+const ROWS: usize = 50;
+const REPORT_FRAMES: u32 = 120;
+
+#[derive(Debug, Default)]
+struct Model {
+    angle: f32,
+}
+
+#[derive(Clone)]
+pub enum Msg {
+    Tick,
+}
+
+impl Component for Model {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: &Self::Properties) -> Self {
+        Model::default()
+    }
+
+    #[allow(irrefutable_let_patterns)]
+    fn system_update(&mut self, msg: SystemMessage) -> Option<Self::Message> {
+        if let SystemMessage::FrameChange = msg {
+            Some(Msg::Tick)
+        } else {
+            None
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ChangeView {
+        match msg {
+            Msg::Tick => {
+                self.angle += 0.01;
+                ChangeView::Modify
+            },
+        }
+    }
+
+    fn view(&self) -> Node<Self> {
+        egml! {
+            <group>
+                { for (0..COLS * ROWS).map(|n| self.view_cell(n)) }
+
+                <group translate = (480, 400), >
+                    <path cmd = vec![Move([0.0, 0.0]), Line([0.0, -300.0]), Close],
+                        stroke = (Color::Red, 3),
+                        transform = Transform::new().with_rotation(self.angle),
+                        modifier = |this, model: Model| { this.transform.as_mut().map(|t| t.rotate(model.angle)); }, />
+                </group>
+            </group>
+        }
+    }
+}
+
+impl Model {
+    fn view_cell(&self, n: usize) -> Node<Model> {
+        let (col, row) = ((n % COLS) as f32, (n / COLS) as f32);
+        egml! {
+            <rect stroke = (Color::Blue, 1), >
+                <text x = col * 24.0, y = row * 16.0, font_name = "Roboto", font_size = 10,
+                        align = (Left, Top), fill = Color::White, >
+                    { format!("{}", n) }
+                </text>
+            </rect>
+        }
+    }
+}
+
+fn main() {
+    let use_cache = !env::args().any(|arg| arg == "--no-cache");
+
+    let renderer = if use_cache {
+        NanovgRenderer::default().with_stats().with_recalc_cache()
+    } else {
+        NanovgRenderer::default().with_stats()
+    };
+
+    let mut app = App::new(
+        glutin::WindowBuilder::new()
+            .with_title("ExGUI recalc bench")
+            .with_dimensions(960, 800),
+        glutin::ContextBuilder::new()
+            .with_vsync(false)
+            .with_srgb(true),
+        renderer,
+    ).unwrap();
+
+    app.init().unwrap();
+    app.renderer_mut().load_font("Roboto", "resources/Roboto-Regular.ttf").unwrap();
+
+    let mut comp = Comp::new::<Model>(());
+    comp.resolve(None);
+
+    let mut frames = 0;
+    let mut elapsed = Duration::default();
+    let mut last = Instant::now();
+
+    app.run_proc(&mut comp, move |app, _| {
+        let now = Instant::now();
+        elapsed += now - last;
+        last = now;
+        frames += 1;
+
+        if frames == REPORT_FRAMES {
+            let frame_time = elapsed / REPORT_FRAMES;
+            println!(
+                "{} nodes, recalc cache {}: {:.3} ms per frame",
+                COLS * ROWS * 3 + 3,
+                if use_cache { "on" } else { "off" },
+                frame_time.as_secs() as f64 * 1e3 + f64::from(frame_time.subsec_nanos()) / 1e6,
+            );
+            if let Some(stats) = app.renderer_mut().last_stats() {
+                println!("last frame: {:?}", stats);
+            }
+            frames = 0;
+            elapsed = Duration::default();
+        }
+
+        let (dims, hdpi) = (app.dimensions(), app.window().hidpi_factor());
+        app.renderer_mut().set_dimensions(dims, hdpi);
+        AppState::Continue
+    }).unwrap();
+}
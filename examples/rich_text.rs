@@ -0,0 +1,81 @@
+use exgui_renderer_nanovg::{NanovgRenderer, NodeStyle, Span, TextFlow, FontWeight, TextDecoration};
+use exgui_controller_glutin::{App, AppState, glutin};
+use exgui::{egml, Component, ChangeView, Node, Comp, Color, Shape, AlignHor::*, AlignVer::*};
+
+#[derive(Debug)]
+struct Model;
+
+impl Component for Model {
+    type Message = ();
+    type Properties = ();
+
+    fn create(_props: &Self::Properties) -> Self {
+        Model
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ChangeView {
+        ChangeView::None
+    }
+
+    fn view(&self) -> Node<Self> {
+        egml! {
+            <rect x = 40, y = 40, width = 400, height = 200, stroke = (Color::Blue, 2), >
+                <text x = 50, y = 80, font_name = "Roboto", font_size = 24,
+                        align = (Left, Baseline), fill = Color::White, >
+                    { "Rich text is made of " }
+                    { "spans" }
+                    { " that flow on a shared baseline and wrap at the width of the parent." }
+                </text>
+            </rect>
+        }
+    }
+}
+
+fn main() {
+    let mut app = App::new(
+        glutin::WindowBuilder::new()
+            .with_title("ExGUI rich text")
+            .with_dimensions(480, 480),
+        glutin::ContextBuilder::new()
+            .with_vsync(true)
+            .with_multisampling(8)
+            .with_srgb(true),
+        NanovgRenderer::default()
+    ).unwrap();
+
+    app.init().unwrap();
+    app.renderer_mut().load_font("Roboto", "resources/Roboto-Regular.ttf").unwrap();
+
+    let mut comp = Comp::new::<Model>(());
+    comp.resolve(None);
+
+    let text_path = NanovgRenderer::find_path(&comp, |shape| if let Shape::Text(_) = shape {
+        true
+    } else {
+        false
+    }).unwrap();
+    let mut span_path = text_path.clone();
+    span_path.push(1);
+
+    app.renderer_mut().set_style(text_path, NodeStyle {
+        flow: Some(TextFlow { max_width: Some(380.0) }),
+        ..Default::default()
+    });
+    app.renderer_mut().set_style(span_path, NodeStyle {
+        span: Some(Span {
+            font_size: Some(32.0),
+            color: Some(Color::Yellow),
+            weight: Some(FontWeight::Bold),
+            decoration: Some(TextDecoration { underline: true, ..Default::default() }),
+            letter_spacing: Some(1.5),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    app.run_proc(&mut comp, |app, _| {
+        let (dims, hdpi) = (app.dimensions(), app.window().hidpi_factor());
+        app.renderer_mut().set_dimensions(dims, hdpi);
+        AppState::Continue
+    }).unwrap();
+}
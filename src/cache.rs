@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use exgui::{Real, RealValue, Drawable, Shape, Text, AlignHor, AlignVer, Transform, Stroke, LineCap, LineJoin};
+use crate::BoundingBox;
+use crate::path::{self, Segment};
+
+/// Position of a node in the tree as a list of child indices from the root.
+/// It changes when an earlier sibling of the node or of an ancestor is inserted or removed,
+/// so state keyed by it, such as a `NodeStyle`, needs a tree of stable shape.
+pub type NodePath = Vec<usize>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CachedNode {
+    fingerprint: u64,
+    parent_bound: BoundingBox,
+    text: Option<u64>,
+    bound: BoundingBox,
+}
+
+/// Retained state of the recalc pass between frames.
+///
+/// Before the recalc pass every subtree is fingerprinted from its layout-relevant fields.
+/// A subtree whose fingerprint, parent bound and inherited text settings are the same
+/// as before the previous recalc is skipped and its previous bound is reused, so a node
+/// whose recalc changes its own fields, e.g. resolving a percentage for the first time,
+/// is recalculated once more before it is reused.
+/// Measured words are cached by content and text settings.
+#[derive(Debug, Default)]
+pub struct RecalcCache {
+    nodes: HashMap<NodePath, CachedNode>,
+    fingerprints: HashMap<NodePath, u64>,
+    words: HashMap<u64, BoundingBox>,
+    prev_words: HashMap<u64, BoundingBox>,
+}
+
+impl RecalcCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.fingerprints.clear();
+        self.words.clear();
+        self.prev_words.clear();
+    }
+
+    pub(crate) fn begin_frame(&mut self, root: &dyn Drawable) {
+        self.fingerprints.clear();
+        Self::fingerprint(root, &mut Vec::new(), &mut self.fingerprints);
+        self.prev_words = mem::take(&mut self.words);
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        let fingerprints = &self.fingerprints;
+        self.nodes.retain(|path, _| fingerprints.contains_key(path));
+        self.prev_words.clear();
+    }
+
+    /// Forgets the nodes whose layout depends on the style at `path`: the node, its ancestors,
+    /// whose bounds enclose it, and its descendants, which inherit its text settings.
+    /// Measured words don't depend on styles and are kept.
+    pub(crate) fn invalidate(&mut self, path: &[usize]) {
+        self.nodes.retain(|node_path, _| !node_path.starts_with(path) && !path.starts_with(node_path));
+    }
+
+    /// Whether `path` was in the tree at the last `begin_frame`.
+    pub(crate) fn contains(&self, path: &[usize]) -> bool {
+        self.fingerprints.contains_key(path)
+    }
+
+    pub(crate) fn reuse(&self, path: &NodePath, parent_bound: BoundingBox, text: Option<&Text>) -> Option<BoundingBox> {
+        let fingerprint = *self.fingerprints.get(path)?;
+        let node = self.nodes.get(path)?;
+        if node.fingerprint == fingerprint
+            && node.parent_bound == parent_bound
+            && node.text == text.map(Self::text_fingerprint)
+        {
+            Some(node.bound)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn store(&mut self, path: &NodePath, parent_bound: BoundingBox, text: Option<&Text>, bound: BoundingBox) {
+        self.nodes.insert(path.clone(), CachedNode {
+            fingerprint: self.fingerprints.get(path).cloned().unwrap_or_default(),
+            parent_bound,
+            text: text.map(Self::text_fingerprint),
+            bound,
+        });
+    }
+
+    pub(crate) fn word_bound<F>(&mut self, text: &Text, content: &str, measure: F) -> BoundingBox
+    where
+        F: FnOnce() -> BoundingBox,
+    {
+        let mut hasher = DefaultHasher::new();
+        Self::text_fingerprint(text).hash(&mut hasher);
+        content.hash(&mut hasher);
+        let key = hasher.finish();
+
+        if let Some(bound) = self.words.get(&key) {
+            return *bound;
+        }
+        let bound = self.prev_words.remove(&key).unwrap_or_else(measure);
+        self.words.insert(key, bound);
+        bound
+    }
+
+    fn fingerprint(draw: &dyn Drawable, path: &mut NodePath, out: &mut HashMap<NodePath, u64>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        if let Some(shape) = draw.shape() {
+            Self::hash_shape(shape, &mut hasher);
+        }
+        if let Some(childs) = draw.childs() {
+            for (idx, child) in childs.into_iter().enumerate() {
+                path.push(idx);
+                Self::fingerprint(child, path, out).hash(&mut hasher);
+                path.pop();
+            }
+        }
+        let fingerprint = hasher.finish();
+        out.insert(path.clone(), fingerprint);
+        fingerprint
+    }
+
+    fn text_fingerprint(text: &Text) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        Self::hash_text(text, &mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_shape<H: Hasher>(shape: &Shape, state: &mut H) {
+        mem::discriminant(shape).hash(state);
+        match shape {
+            Shape::Rect(r) => {
+                Self::hash_values(&[&r.x, &r.y, &r.width, &r.height], state);
+                Self::hash_stroke(r.stroke.as_ref(), state);
+                Self::hash_transform(r.transform.as_ref(), state);
+            },
+            Shape::Circle(c) => {
+                Self::hash_values(&[&c.cx, &c.cy, &c.r], state);
+                Self::hash_stroke(c.stroke.as_ref(), state);
+                Self::hash_transform(c.transform.as_ref(), state);
+            },
+            Shape::Text(t) => Self::hash_text(t, state),
+            Shape::Word(w) => {
+                let content: &str = w.as_ref();
+                content.hash(state);
+            },
+            Shape::Path(p) => {
+                for segment in path::segments(&p.cmd) {
+                    Self::hash_segment(&segment, state);
+                }
+                Self::hash_stroke(p.stroke.as_ref(), state);
+                Self::hash_transform(p.transform.as_ref(), state);
+            },
+            Shape::Group(_) => (),
+        }
+    }
+
+    fn hash_text<H: Hasher>(text: &Text, state: &mut H) {
+        text.font_name.hash(state);
+        Self::hash_values(&[&text.x, &text.y, &text.font_size], state);
+        let align_hor: u8 = match text.align.0 {
+            AlignHor::Left => 0,
+            AlignHor::Center => 1,
+            AlignHor::Right => 2,
+        };
+        let align_ver: u8 = match text.align.1 {
+            AlignVer::Top => 0,
+            AlignVer::Middle => 1,
+            AlignVer::Baseline => 2,
+            AlignVer::Bottom => 3,
+        };
+        (align_hor, align_ver).hash(state);
+        Self::hash_transform(text.transform.as_ref(), state);
+    }
+
+    fn hash_segment<H: Hasher>(segment: &Segment, state: &mut H) {
+        mem::discriminant(segment).hash(state);
+        match *segment {
+            Segment::Move(to) | Segment::Line(to) => Self::hash_reals(&[to.0, to.1], state),
+            Segment::Quad(ctrl, to) => Self::hash_reals(&[ctrl.0, ctrl.1, to.0, to.1], state),
+            Segment::Cubic(ctrl1, ctrl2, to) => {
+                Self::hash_reals(&[ctrl1.0, ctrl1.1, ctrl2.0, ctrl2.1, to.0, to.1], state);
+            },
+            Segment::Close => (),
+            Segment::Unsupported(idx) => idx.hash(state),
+        }
+    }
+
+    fn hash_stroke<H: Hasher>(stroke: Option<&Stroke>, state: &mut H) {
+        stroke.is_some().hash(state);
+        if let Some(stroke) = stroke {
+            let line_cap: u8 = match stroke.line_cap {
+                LineCap::Butt => 0,
+                LineCap::Round => 1,
+                LineCap::Square => 2,
+            };
+            let line_join: u8 = match stroke.line_join {
+                LineJoin::Miter => 0,
+                LineJoin::Round => 1,
+                LineJoin::Bevel => 2,
+            };
+            (line_cap, line_join).hash(state);
+            Self::hash_reals(&[stroke.width, stroke.miter_limit], state);
+        }
+    }
+
+    fn hash_transform<H: Hasher>(transform: Option<&Transform>, state: &mut H) {
+        transform.is_some().hash(state);
+        if let Some(transform) = transform {
+            transform.absolute.hash(state);
+            Self::hash_reals(&transform.matrix, state);
+        }
+    }
+
+    /// A value with its unit: a fixed value, a percentage and an auto value of the same
+    /// number lay out differently. Only percentage and auto values are resolved.
+    fn hash_values<H: Hasher>(values: &[&RealValue], state: &mut H) {
+        for value in values {
+            let unit: u8 = if value.clone().set_by_pct(1.0) {
+                1
+            } else if value.clone().set_by_auto(0.0) {
+                2
+            } else {
+                0
+            };
+            unit.hash(state);
+            value.val().to_bits().hash(state);
+        }
+    }
+
+    fn hash_reals<H: Hasher>(values: &[Real], state: &mut H) {
+        for value in values {
+            value.to_bits().hash(state);
+        }
+    }
+}
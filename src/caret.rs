@@ -0,0 +1,234 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+use exgui::{Real, Text, AlignHor};
+use crate::BoundingBox;
+use crate::measure::{TextMeasurer, Measure, GlyphPosition};
+use crate::shaping::{Shaped, Order};
+use crate::text::{self, SpanSettings, TextRun, ELLIPSIS};
+
+/// Glyph geometry of a `Word` as it is laid out, one line per `TextRun`.
+/// Indices are byte indices into the logical word content; the glyphs are laid out
+/// in the shaped content and the content an ellipsis stands for has no glyphs of its own.
+pub(crate) struct WordGeometry {
+    glyphs: Vec<GlyphPosition>,
+    lines: Vec<Line>,
+    order: Order,
+}
+
+/// The content range a run shows and the range of its glyphs.
+struct Line {
+    bytes: Range<usize>,
+    glyphs: Range<usize>,
+    end_x: Real,
+    min_y: Real,
+    max_y: Real,
+}
+
+/// Maps the byte indices of a run onto the word content: a run is a slice of the content
+/// or, shortened by an overflow, a head and a tail of the content around an ellipsis.
+struct RunMap {
+    start: usize,
+    end: usize,
+    head_len: usize,
+    tail_len: usize,
+    run_len: usize,
+}
+
+impl RunMap {
+    fn new(content: &str, offset: usize, run: &str) -> Self {
+        if let Some(found) = content[offset..].find(run) {
+            let start = offset + found;
+            return RunMap { start, end: start + run.len(), head_len: run.len(), tail_len: 0, run_len: run.len() };
+        }
+
+        let head_len = run.find(ELLIPSIS).unwrap_or_else(|| run.len());
+        let tail_len = run.len() - (head_len + ELLIPSIS.len()).min(run.len());
+        RunMap { start: offset, end: content.len(), head_len, tail_len, run_len: run.len() }
+    }
+
+    fn index(&self, run_index: usize) -> usize {
+        if run_index < self.head_len {
+            self.start + run_index
+        } else if run_index < self.run_len - self.tail_len {
+            self.start + self.head_len
+        } else {
+            self.end - (self.run_len - run_index)
+        }
+    }
+}
+
+impl WordGeometry {
+    /// Geometry of the `shaped` word laid out in `runs`, or placed at the `text` position
+    /// when it has no runs of its own.
+    pub(crate) fn of_word(measurer: &dyn TextMeasurer,
+                          text: &Text,
+                          settings: &SpanSettings,
+                          shaped: &Shaped,
+                          runs: Option<&[TextRun]>) -> Self
+    {
+        let measure = settings.measure(measurer);
+        let content: &str = &shaped.content;
+        let geometry = match runs {
+            Some(runs) => Self::new(&measure, content, runs),
+            None => Self::new(&measure, content, &[text::word_run(measurer, text, settings, content)]),
+        };
+        WordGeometry { order: shaped.order.clone(), ..geometry }
+    }
+
+    pub(crate) fn new(measure: &Measure, content: &str, runs: &[TextRun]) -> Self {
+        let metrics = measure.metrics();
+        let mut glyphs = Vec::new();
+        let mut lines = Vec::new();
+        let mut offset = 0;
+
+        for run in runs {
+            let map = RunMap::new(content, offset, &run.content);
+            let first = glyphs.len();
+            glyphs.extend(
+                measure.glyph_positions(run.x, &run.content, &AlignHor::Left)
+                    .into_iter()
+                    .map(|glyph| GlyphPosition { byte_index: map.index(glyph.byte_index), ..glyph })
+            );
+            lines.push(Line {
+                bytes: map.start..map.end,
+                glyphs: first..glyphs.len(),
+                end_x: run.x + run.advance,
+                min_y: run.y - metrics.ascender,
+                max_y: run.y - metrics.descender,
+            });
+            offset = map.end;
+        }
+
+        WordGeometry { glyphs, lines, order: Order::default() }
+    }
+
+    pub(crate) fn glyphs(&self) -> Vec<GlyphPosition> {
+        self.glyphs.iter()
+            .map(|glyph| GlyphPosition { byte_index: self.order.logical_index(glyph.byte_index), ..*glyph })
+            .collect()
+    }
+
+    /// The last line starting at or before `index`; content skipped between lines,
+    /// such as the spaces at a wrap, belongs to the end of the line before.
+    fn line(&self, index: usize) -> Option<&Line> {
+        self.lines.iter().rev()
+            .find(|line| line.bytes.start <= index)
+            .or_else(|| self.lines.first())
+    }
+
+    fn x_at(&self, line: &Line, index: usize) -> Real {
+        self.glyphs[line.glyphs.clone()].iter()
+            .find(|glyph| glyph.byte_index >= index)
+            .map_or(line.end_x, |glyph| glyph.min_x)
+    }
+
+    pub(crate) fn caret(&self, index: usize, width: Real) -> Option<BoundingBox> {
+        let index = self.order.visual_caret(index);
+        let line = self.line(index)?;
+        let x = self.x_at(line, index);
+        Some(BoundingBox {
+            min_x: x - width / 2.0,
+            min_y: line.min_y,
+            max_x: x + width / 2.0,
+            max_y: line.max_y,
+        })
+    }
+
+    /// One rect per line the range is on, and per piece of it the bidi reorder separates.
+    pub(crate) fn selection(&self, start: usize, end: usize) -> Vec<BoundingBox> {
+        let (start, end) = (start.min(end), start.max(end));
+        self.order.visual_ranges(start, end)
+            .into_iter()
+            .flat_map(|range| self.visual_selection(range.start, range.end))
+            .collect()
+    }
+
+    fn visual_selection(&self, start: usize, end: usize) -> Vec<BoundingBox> {
+        self.lines.iter()
+            .filter_map(|line| {
+                let (from, to) = (start.max(line.bytes.start), end.min(line.bytes.end));
+                if from >= to {
+                    return None;
+                }
+                Some(BoundingBox {
+                    min_x: self.x_at(line, from),
+                    min_y: line.min_y,
+                    max_x: self.x_at(line, to),
+                    max_y: line.max_y,
+                })
+            })
+            .collect()
+    }
+
+    /// Index of the caret position nearest to `(x, y)` on the line nearest to `y`.
+    pub(crate) fn index_at(&self, (x, y): (Real, Real)) -> usize {
+        let distance = |line: &&Line| if y < line.min_y {
+            line.min_y - y
+        } else {
+            (y - line.max_y).max(0.0)
+        };
+        let line = match self.lines.iter().min_by(|a, b| {
+            distance(a).partial_cmp(&distance(b)).unwrap_or(Ordering::Equal)
+        }) {
+            Some(line) => line,
+            None => return 0,
+        };
+
+        let index = self.glyphs[line.glyphs.clone()].iter()
+            .find(|glyph| x < (glyph.min_x + glyph.max_x) / 2.0)
+            .map_or(line.bytes.end, |glyph| glyph.byte_index);
+        self.order.logical_caret(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{FONT, measurer};
+
+    fn run(measure: &Measure, (x, y): (Real, Real), content: &str) -> TextRun {
+        TextRun {
+            x,
+            y,
+            advance: measure.advance(content),
+            content: content.to_string(),
+            bound: BoundingBox::default(),
+            decorations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn selection_spans_the_lines_of_a_flowed_word() {
+        let measurer = measurer();
+        let measure = Measure::new(&measurer, FONT, 20.0);
+        // "hello world" wrapped after the space, which the flow drops
+        let runs = [run(&measure, (10.0, 30.0), "hello"), run(&measure, (10.0, 60.0), "world")];
+        let geometry = WordGeometry::new(&measure, "hello world", &runs);
+
+        let rects = geometry.selection(2, 8);
+        assert_eq!(rects.len(), 2);
+        assert!(rects[0].max_y <= rects[1].min_y + 1e-3);
+        assert_eq!(rects[0].max_x, runs[0].x + runs[0].advance);
+        assert_eq!(rects[1].min_x, 10.0);
+        assert!(rects[1].max_x > 10.0);
+
+        let second_line = geometry.caret(6, 1.0).expect("The word has lines");
+        assert!(second_line.min_y > 30.0);
+        assert_eq!(geometry.index_at((10.0, 55.0)), 6);
+        assert_eq!(geometry.index_at((1000.0, 25.0)), 5);
+    }
+
+    #[test]
+    fn elided_content_maps_to_the_ellipsis() {
+        let measurer = measurer();
+        let measure = Measure::new(&measurer, FONT, 20.0);
+        let content = "abcdefgh";
+        let shortened = format!("ab{}gh", ELLIPSIS);
+        let geometry = WordGeometry::new(&measure, content, &[run(&measure, (0.0, 20.0), &shortened)]);
+
+        let indices: Vec<_> = geometry.glyphs().iter().map(|glyph| glyph.byte_index).collect();
+        assert_eq!(indices, vec![0, 1, 2, 6, 7]);
+        let end = geometry.caret(content.len(), 0.0).expect("The word has lines");
+        assert_eq!(end.min_x, measure.advance(&shortened));
+    }
+}
@@ -0,0 +1,207 @@
+use nanovg::{Frame, Font as NanovgFont, Color as NanovgColor, StrokeOptions, TextOptions, Alignment};
+use exgui::{Real, Shape};
+use crate::{BoundingBox, NodePath};
+use crate::layer::PaintedNode;
+
+const MARKER_SIZE: Real = 4.0;
+
+/// Overlay of the node bounds computed by the recalc pass, drawn on top of the frame where
+/// the draw pass painted the nodes.
+/// Labels with the node path and size are drawn when `label_font` names a loaded font.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugOverlay {
+    pub label_font: Option<String>,
+    pub label_size: Real,
+    pub stroke_width: Real,
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        DebugOverlay {
+            label_font: None,
+            label_size: 10.0,
+            stroke_width: 1.0,
+        }
+    }
+}
+
+impl DebugOverlay {
+    pub fn with_labels<S: Into<String>>(mut self, font_name: S) -> Self {
+        self.label_font = Some(font_name.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ShapeKind {
+    Rect,
+    Circle,
+    Path,
+    Text,
+    Word,
+    Group,
+    None,
+}
+
+impl ShapeKind {
+    pub(crate) fn of(shape: Option<&Shape>) -> Self {
+        match shape {
+            Some(Shape::Rect(_)) => ShapeKind::Rect,
+            Some(Shape::Circle(_)) => ShapeKind::Circle,
+            Some(Shape::Path(_)) => ShapeKind::Path,
+            Some(Shape::Text(_)) => ShapeKind::Text,
+            Some(Shape::Word(_)) => ShapeKind::Word,
+            Some(Shape::Group(_)) => ShapeKind::Group,
+            None => ShapeKind::None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ShapeKind::Rect => "Rect",
+            ShapeKind::Circle => "Circle",
+            ShapeKind::Path => "Path",
+            ShapeKind::Text => "Text",
+            ShapeKind::Word => "Word",
+            ShapeKind::Group => "Group",
+            ShapeKind::None => "Node",
+        }
+    }
+
+    fn color(self) -> NanovgColor {
+        match self {
+            ShapeKind::Rect => NanovgColor::new(0.2, 0.5, 1.0, 0.9),
+            ShapeKind::Circle => NanovgColor::new(0.2, 0.8, 0.3, 0.9),
+            ShapeKind::Path => NanovgColor::new(1.0, 0.6, 0.1, 0.9),
+            ShapeKind::Text => NanovgColor::new(0.9, 0.2, 0.9, 0.9),
+            ShapeKind::Word => NanovgColor::new(0.6, 0.4, 1.0, 0.9),
+            ShapeKind::Group | ShapeKind::None => NanovgColor::new(0.6, 0.6, 0.6, 0.9),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NodeBound {
+    pub path: NodePath,
+    pub kind: ShapeKind,
+    pub bound: BoundingBox,
+}
+
+impl NodeBound {
+    fn is_degenerate(&self) -> bool {
+        !(self.bound.width() > 0.0 && self.bound.height() > 0.0)
+    }
+
+    fn label(&self) -> String {
+        let id = self.path.iter().map(|idx| idx.to_string()).collect::<Vec<_>>().join(".");
+        format!("{} [{}] {:.1}x{:.1}", self.kind.name(), id, self.bound.width(), self.bound.height())
+    }
+}
+
+/// Bounds of the nodes drawn by the last render in paint order, at their scroll offsets and
+/// clipped to the viewports they were drawn in. Nodes scrolled out of their viewports are left
+/// out, as are hidden and collapsed ones, which are not drawn.
+pub(crate) fn node_bounds(painted: &[PaintedNode]) -> Vec<NodeBound> {
+    painted.iter()
+        .filter_map(|node| {
+            let bound = match node.clip {
+                Some(clip) => {
+                    let outside = node.bound.max_x < clip.min_x || node.bound.min_x > clip.max_x
+                        || node.bound.max_y < clip.min_y || node.bound.min_y > clip.max_y;
+                    if outside {
+                        return None;
+                    }
+                    node.bound.intersection(&clip)
+                },
+                None => node.bound,
+            };
+            Some(NodeBound { path: node.path.clone(), kind: node.kind, bound })
+        })
+        .collect()
+}
+
+/// Degenerate bounds, with zero or negative size, are drawn in red with a cross marker.
+pub(crate) fn draw_overlay(frame: &Frame, overlay: &DebugOverlay, bounds: &[NodeBound]) {
+    let degenerate_color = NanovgColor::new(1.0, 0.0, 0.0, 1.0);
+
+    for node in bounds {
+        let BoundingBox { min_x, min_y, .. } = node.bound;
+        let degenerate = node.is_degenerate();
+        let color = if degenerate { degenerate_color } else { node.kind.color() };
+
+        frame.path(
+            |path| {
+                if degenerate {
+                    path.move_to((min_x - MARKER_SIZE, min_y - MARKER_SIZE));
+                    path.line_to((min_x + MARKER_SIZE, min_y + MARKER_SIZE));
+                    path.move_to((min_x - MARKER_SIZE, min_y + MARKER_SIZE));
+                    path.line_to((min_x + MARKER_SIZE, min_y - MARKER_SIZE));
+                }
+                path.rect((min_x, min_y), (node.bound.width().max(0.0), node.bound.height().max(0.0)));
+                path.stroke(color, StrokeOptions {
+                    width: overlay.stroke_width,
+                    ..Default::default()
+                });
+            },
+            Default::default(),
+        );
+
+        if let Some(ref font_name) = overlay.label_font {
+            if let Ok(font) = NanovgFont::find(frame.context(), font_name) {
+                frame.text(
+                    font,
+                    (min_x + 1.0, min_y + 1.0),
+                    &node.label(),
+                    TextOptions {
+                        color,
+                        size: overlay.label_size,
+                        align: Alignment::new().left().top(),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn painted(path: &[usize],
+               kind: ShapeKind,
+               (min_x, min_y, max_x, max_y): (Real, Real, Real, Real),
+               clip: Option<BoundingBox>) -> PaintedNode
+    {
+        PaintedNode { path: path.to_vec(), kind, bound: BoundingBox { min_x, min_y, max_x, max_y }, clip }
+    }
+
+    #[test]
+    fn overlay_follows_the_scroll_offsets_and_clips_of_the_painted_nodes() {
+        let viewport = BoundingBox { min_x: 0.0, min_y: 0.0, max_x: 100.0, max_y: 50.0 };
+        let nodes = [
+            painted(&[0], ShapeKind::Group, (0.0, -40.0, 100.0, 160.0), None),
+            painted(&[0, 0], ShapeKind::Rect, (10.0, -30.0, 60.0, 20.0), Some(viewport)),
+            painted(&[0, 1], ShapeKind::Circle, (10.0, 90.0, 30.0, 110.0), Some(viewport)),
+            painted(&[0, 2, 0], ShapeKind::Word, (0.0, 20.0, 40.0, 30.0), Some(viewport)),
+            painted(&[0, 2, 0], ShapeKind::Word, (0.0, 30.0, 40.0, 40.0), Some(viewport)),
+        ];
+
+        let bounds = node_bounds(&nodes);
+        let summary: Vec<_> = bounds.iter().map(|node| (node.path.clone(), node.kind, node.bound)).collect();
+        assert_eq!(summary, vec![
+            (vec![0], ShapeKind::Group, nodes[0].bound),
+            (vec![0, 0], ShapeKind::Rect, BoundingBox { min_x: 10.0, min_y: 0.0, max_x: 60.0, max_y: 20.0 }),
+            (vec![0, 2, 0], ShapeKind::Word, nodes[3].bound),
+            (vec![0, 2, 0], ShapeKind::Word, nodes[4].bound),
+        ]);
+    }
+
+    #[test]
+    fn groups_leave_their_points_to_the_nodes_below() {
+        let group = painted(&[0], ShapeKind::Group, (0.0, 0.0, 100.0, 100.0), None);
+        let rect = painted(&[0, 0], ShapeKind::Rect, (0.0, 0.0, 100.0, 100.0), None);
+        assert!(!group.contains((50.0, 50.0)));
+        assert!(rect.contains((50.0, 50.0)));
+    }
+}
@@ -0,0 +1,57 @@
+use exgui::Real;
+
+/// Ratio of physical device pixels to the logical pixels that layout coordinates,
+/// font sizes and stroke widths are given in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceScale {
+    ratio: Real,
+}
+
+impl Default for DeviceScale {
+    fn default() -> Self {
+        DeviceScale { ratio: 1.0 }
+    }
+}
+
+impl DeviceScale {
+    /// A ratio that is not positive, e.g. of a renderer without dimensions, counts as 1.
+    pub fn new(device_pixel_ratio: Real) -> Self {
+        if device_pixel_ratio.is_finite() && device_pixel_ratio > 0.0 {
+            DeviceScale { ratio: device_pixel_ratio }
+        } else {
+            DeviceScale::default()
+        }
+    }
+
+    pub fn ratio(&self) -> Real {
+        self.ratio
+    }
+
+    pub fn to_physical(&self, logical: Real) -> Real {
+        logical * self.ratio
+    }
+
+    pub fn to_logical(&self, physical: Real) -> Real {
+        physical / self.ratio
+    }
+
+    pub fn to_logical_size(&self, (width, height): (u32, u32)) -> (Real, Real) {
+        (self.to_logical(width as Real), self.to_logical(height as Real))
+    }
+
+    /// The logical size in whole device pixels.
+    pub fn to_physical_size(&self, (width, height): (Real, Real)) -> (u32, u32) {
+        let physical = |logical: Real| self.to_physical(logical.max(0.0)).round() as u32;
+        (physical(width), physical(height))
+    }
+
+    /// The logical value nearest to `logical` that lies on the device pixel grid.
+    pub fn round(&self, logical: Real) -> Real {
+        self.to_logical(self.to_physical(logical).round())
+    }
+
+    /// Width of a line one device pixel wide.
+    pub fn hairline(&self) -> Real {
+        self.to_logical(1.0)
+    }
+}
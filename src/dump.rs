@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use exgui::{Real, Drawable, Shape, Transform};
+use crate::{BoundingBox, NodePath};
+use crate::debug::ShapeKind;
+use crate::text::TextLayouts;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutValue {
+    Number(Real),
+    Text(String),
+}
+
+/// A node of the tree after the recalc pass with its resolved properties.
+/// `bound` is where the node is placed and `None` for nodes the recalc pass did not reach.
+/// `props` are the properties the node resolved to in the coordinates of its parent, before
+/// the content of anchored ancestors is moved: the child at `x` 10 of an auto sized rect
+/// anchored 340 pixels to the right keeps `x` 10 in `props` but has its bound at 350.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutNode {
+    pub kind: &'static str,
+    pub path: NodePath,
+    pub bound: Option<BoundingBox>,
+    pub props: Vec<(&'static str, LayoutValue)>,
+    pub transform: Option<[Real; 6]>,
+    pub children: Vec<LayoutNode>,
+}
+
+impl LayoutNode {
+    /// One line per node, children indented by two spaces.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        self.write_text(&mut out, 0);
+        out
+    }
+
+    /// Pretty-printed JSON with a fixed key order and two decimals for numbers.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out, 0);
+        out.push('\n');
+        out
+    }
+
+    fn write_text(&self, out: &mut String, depth: usize) {
+        let _ = write!(out, "{:indent$}{} [{}]", "", self.kind, path_id(&self.path), indent = depth * 2);
+        if let Some(ref bound) = self.bound {
+            let _ = write!(
+                out, " bound=({}, {}, {}, {})",
+                number(bound.min_x), number(bound.min_y), number(bound.max_x), number(bound.max_y),
+            );
+        }
+        for (name, value) in self.props.iter() {
+            match *value {
+                LayoutValue::Number(number_value) => { let _ = write!(out, " {}={}", name, number(number_value)); },
+                LayoutValue::Text(ref text) => { let _ = write!(out, " {}={:?}", name, text); },
+            }
+        }
+        if let Some(ref matrix) = self.transform {
+            let matrix: Vec<_> = matrix.iter().map(|&value| number(value)).collect();
+            let _ = write!(out, " transform=[{}]", matrix.join(", "));
+        }
+        out.push('\n');
+
+        for child in self.children.iter() {
+            child.write_text(out, depth + 1);
+        }
+    }
+
+    fn write_json(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth + 1);
+        let path: Vec<_> = self.path.iter().map(|idx| idx.to_string()).collect();
+
+        out.push_str("{\n");
+        let _ = writeln!(out, "{}\"kind\": {},", indent, json_string(self.kind));
+        let _ = writeln!(out, "{}\"path\": [{}],", indent, path.join(", "));
+        match self.bound {
+            Some(ref bound) => {
+                let _ = writeln!(
+                    out, "{}\"bound\": {{\"min_x\": {}, \"min_y\": {}, \"max_x\": {}, \"max_y\": {}}},",
+                    indent, number(bound.min_x), number(bound.min_y), number(bound.max_x), number(bound.max_y),
+                );
+            },
+            None => { let _ = writeln!(out, "{}\"bound\": null,", indent); },
+        }
+
+        let props: Vec<_> = self.props.iter()
+            .map(|(name, value)| match *value {
+                LayoutValue::Number(number_value) => format!("{}: {}", json_string(name), number(number_value)),
+                LayoutValue::Text(ref text) => format!("{}: {}", json_string(name), json_string(text)),
+            })
+            .collect();
+        let _ = writeln!(out, "{}\"props\": {{{}}},", indent, props.join(", "));
+
+        match self.transform {
+            Some(ref matrix) => {
+                let matrix: Vec<_> = matrix.iter().map(|&value| number(value)).collect();
+                let _ = writeln!(out, "{}\"transform\": [{}],", indent, matrix.join(", "));
+            },
+            None => { let _ = writeln!(out, "{}\"transform\": null,", indent); },
+        }
+
+        if self.children.is_empty() {
+            let _ = writeln!(out, "{}\"children\": []", indent);
+        } else {
+            let _ = writeln!(out, "{}\"children\": [", indent);
+            for (idx, child) in self.children.iter().enumerate() {
+                out.push_str(&"  ".repeat(depth + 2));
+                child.write_json(out, depth + 2);
+                out.push_str(if idx + 1 < self.children.len() { ",\n" } else { "\n" });
+            }
+            let _ = writeln!(out, "{}]", indent);
+        }
+        let _ = write!(out, "{}}}", "  ".repeat(depth));
+    }
+}
+
+/// Rounds to two decimals without negative zeros so dumps stay stable.
+fn number(value: Real) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    format!("{:.2}", if rounded == 0.0 { 0.0 } else { rounded })
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            ch if (ch as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", ch as u32); },
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn path_id(path: &[usize]) -> String {
+    path.iter().map(|idx| idx.to_string()).collect::<Vec<_>>().join(".")
+}
+
+pub(crate) fn layout_tree(draw: &dyn Drawable,
+                          bounds: &HashMap<NodePath, BoundingBox>,
+                          text_layouts: &TextLayouts) -> LayoutNode
+{
+    node(draw, &mut Vec::new(), bounds, text_layouts)
+}
+
+fn node(draw: &dyn Drawable,
+        path: &mut NodePath,
+        bounds: &HashMap<NodePath, BoundingBox>,
+        text_layouts: &TextLayouts) -> LayoutNode
+{
+    let run_bound = text_layouts.get(path.as_slice()).and_then(|runs| {
+        runs.iter().map(|run| run.bound).fold(None, |union: Option<BoundingBox>, bound| {
+            Some(union.map_or(bound, |union| union.union(&bound)))
+        })
+    });
+
+    let mut props = Vec::new();
+    let mut transform = None;
+    {
+        let mut prop = |name, value: Real| props.push((name, LayoutValue::Number(value)));
+        match draw.shape() {
+            Some(Shape::Rect(r)) => {
+                prop("x", r.x.val());
+                prop("y", r.y.val());
+                prop("width", r.width.val());
+                prop("height", r.height.val());
+                transform = matrix(r.transform.as_ref());
+            },
+            Some(Shape::Circle(c)) => {
+                prop("cx", c.cx.val());
+                prop("cy", c.cy.val());
+                prop("r", c.r.val());
+                transform = matrix(c.transform.as_ref());
+            },
+            Some(Shape::Path(p)) => {
+                prop("commands", p.cmd.len() as Real);
+                transform = matrix(p.transform.as_ref());
+            },
+            Some(Shape::Text(t)) => {
+                prop("x", t.x.val());
+                prop("y", t.y.val());
+                prop("font_size", t.font_size.val());
+                transform = matrix(t.transform.as_ref());
+            },
+            Some(Shape::Word(w)) => {
+                let content: &str = w.as_ref();
+                props.push(("content", LayoutValue::Text(content.to_string())));
+            },
+            Some(Shape::Group(_)) | None => (),
+        }
+    }
+
+    let mut children = Vec::new();
+    if let Some(childs) = draw.childs() {
+        for (idx, child) in childs.into_iter().enumerate() {
+            path.push(idx);
+            children.push(node(child, path, bounds, text_layouts));
+            path.pop();
+        }
+    }
+
+    LayoutNode {
+        kind: ShapeKind::of(draw.shape()).name(),
+        path: path.clone(),
+        bound: bounds.get(path.as_slice()).cloned().or(run_bound),
+        props,
+        transform,
+        children,
+    }
+}
+
+fn matrix(transform: Option<&Transform>) -> Option<[Real; 6]> {
+    transform.map(|transform| transform.matrix)
+}
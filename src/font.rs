@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use crate::style::{FontWeight, FontStyle};
+
+const SYNTHETIC_BOLD_WEIGHT: u16 = 600;
+
+#[derive(Debug, Clone, PartialEq)]
+struct FontFace {
+    font_name: String,
+    weight: u16,
+    style: FontStyle,
+}
+
+/// A loaded face chosen for a requested family, weight and style.
+/// Missing bold and italic variants are synthesized by the renderer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ResolvedFont<'a> {
+    pub font_name: &'a str,
+    pub synthetic_bold: bool,
+    pub synthetic_oblique: bool,
+}
+
+/// Faces of font families, each registered under the nanovg font name it was loaded with.
+#[derive(Debug, Clone, Default)]
+pub struct FontFamilies {
+    families: HashMap<String, Vec<FontFace>>,
+}
+
+impl FontFamilies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_face(&mut self, family: &str, font_name: &str, weight: FontWeight, style: FontStyle) {
+        let faces = self.families.entry(family.to_string()).or_insert_with(Vec::new);
+        faces.retain(|face| !(face.weight == weight.value() && face.style == style));
+        faces.push(FontFace {
+            font_name: font_name.to_string(),
+            weight: weight.value(),
+            style,
+        });
+    }
+
+    pub fn contains(&self, family: &str) -> bool {
+        self.families.contains_key(family)
+    }
+
+    /// Resolves `name` to the closest face of the family with that name. A name that is
+    /// not a family is a plain font name, whose bold and italic are always synthetic.
+    pub(crate) fn resolve<'a>(&'a self, name: &'a str, weight: FontWeight, style: FontStyle) -> ResolvedFont<'a> {
+        let face = self.families.get(name).and_then(|faces| {
+            faces.iter().min_by_key(|face| Self::distance(face, weight.value(), style))
+        });
+
+        match face {
+            Some(face) => ResolvedFont {
+                font_name: face.font_name.as_str(),
+                synthetic_bold: weight.value() >= SYNTHETIC_BOLD_WEIGHT && face.weight < SYNTHETIC_BOLD_WEIGHT,
+                synthetic_oblique: style != FontStyle::Normal && face.style == FontStyle::Normal,
+            },
+            None => ResolvedFont {
+                font_name: name,
+                synthetic_bold: weight.value() >= SYNTHETIC_BOLD_WEIGHT,
+                synthetic_oblique: style != FontStyle::Normal,
+            },
+        }
+    }
+
+    /// Style mismatches outweigh any weight difference. Between equally distant weights
+    /// the heavier one wins for bold requests and the lighter one otherwise, as in CSS.
+    fn distance(face: &FontFace, weight: u16, style: FontStyle) -> (bool, u16, bool) {
+        let style_mismatch = face.style != style;
+        let weight_distance = (i32::from(face.weight) - i32::from(weight)).abs() as u16;
+        let wrong_side = if weight >= 500 { face.weight < weight } else { face.weight > weight };
+        (style_mismatch, weight_distance, wrong_side)
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use exgui::Real;
+use crate::BoundingBox;
+use crate::measure::{TextMeasurer, FontMetrics};
+use crate::ttf::{read_u16, read_i16, read_u32, font_offset, table};
+
+/// Measures text with the metrics of TrueType and OpenType files, without a GL context.
+/// It follows fontstash: the font size is the ascender to descender height, advances are
+/// rounded to whole pixels, pairs are kerned with the `kern` table and the bounds span the
+/// padded glyph quads. Fonts without `glyf` outlines (CFF) span the advances instead.
+#[derive(Debug, Clone, Default)]
+pub struct FontFileMeasurer {
+    fonts: HashMap<String, FontFile>,
+}
+
+impl FontFileMeasurer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load_font<S, P>(&mut self, name: S, path: P) -> io::Result<()>
+    where
+        S: Into<String>,
+        P: AsRef<Path>,
+    {
+        let data = fs::read(path)?;
+        self.add_font(name, data)
+    }
+
+    pub fn add_font<S: Into<String>>(&mut self, name: S, data: Vec<u8>) -> io::Result<()> {
+        let font = FontFile::parse(data)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Unsupported font file"))?;
+        self.fonts.insert(name.into(), font);
+        Ok(())
+    }
+}
+
+impl TextMeasurer for FontFileMeasurer {
+    fn has_font(&self, font_name: &str) -> bool {
+        self.fonts.contains_key(font_name)
+    }
+
+    fn metrics(&self, font_name: &str, size: Real) -> Option<FontMetrics> {
+        let font = self.fonts.get(font_name)?;
+        let scale = font.scale(size);
+
+        Some(FontMetrics {
+            ascender: font.ascent * scale,
+            descender: font.descent * scale,
+            line_height: font.line_height() * scale,
+        })
+    }
+
+    fn bounds(&self, font_name: &str, size: Real, letter_spacing: Real, text: &str) -> Option<(Real, BoundingBox)> {
+        let font = self.fonts.get(font_name)?;
+        let scale = font.scale(size);
+        let (mut advance, mut min_x, mut max_x) = (0.0, 0.0, 0.0);
+        for (pen, glyph, next_pen) in font.glyph_pens(scale, letter_spacing, text) {
+            let (quad_min_x, quad_max_x) = font.quad_extent(glyph, scale);
+            min_x = Real::min(min_x, pen + quad_min_x);
+            max_x = Real::max(max_x, pen + quad_max_x);
+            advance = next_pen;
+        }
+
+        let min_y = -font.ascent * scale;
+        Some((advance, BoundingBox {
+            min_x,
+            min_y,
+            max_x,
+            max_y: min_y + font.line_height() * scale,
+        }))
+    }
+
+    fn prefix_advances(&self, font_name: &str, size: Real, letter_spacing: Real, text: &str) -> Option<Vec<Real>> {
+        let font = self.fonts.get(font_name)?;
+        Some(font.pen_positions(font.scale(size), letter_spacing, text).collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FontFile {
+    data: Vec<u8>,
+    ascent: Real,
+    descent: Real,
+    line_gap: Real,
+    advances: Vec<u16>,
+    cmap: Option<(usize, u16)>,
+    kern: Option<usize>,
+    glyf: Option<GlyphOutlines>,
+}
+
+/// Offsets of the `loca` and `glyf` tables and whether `loca` holds long offsets.
+#[derive(Debug, Clone, Copy)]
+struct GlyphOutlines {
+    loca: usize,
+    glyf: usize,
+    long_offsets: bool,
+}
+
+impl FontFile {
+    fn parse(data: Vec<u8>) -> Option<Self> {
+        let font_offset = font_offset(&data)?;
+        let hhea = table(&data, font_offset, b"hhea")?;
+        let hmtx = table(&data, font_offset, b"hmtx")?;
+
+        let num_h_metrics = read_u16(&data, hhea + 34)? as usize;
+        let advances = (0..num_h_metrics)
+            .map(|idx| read_u16(&data, hmtx + idx * 4))
+            .collect::<Option<Vec<_>>>()?;
+
+        let cmap = table(&data, font_offset, b"cmap").and_then(|cmap| Self::cmap_subtable(&data, cmap));
+        let kern = table(&data, font_offset, b"kern").and_then(|kern| Self::kern_pairs(&data, kern));
+        let glyf = Self::glyph_outlines(&data, font_offset);
+
+        Some(FontFile {
+            ascent: Real::from(read_i16(&data, hhea + 4)?),
+            descent: Real::from(read_i16(&data, hhea + 6)?),
+            line_gap: Real::from(read_i16(&data, hhea + 8)?),
+            advances,
+            cmap,
+            kern,
+            glyf,
+            data,
+        })
+    }
+
+    fn glyph_outlines(data: &[u8], font_offset: usize) -> Option<GlyphOutlines> {
+        let head = table(data, font_offset, b"head")?;
+        Some(GlyphOutlines {
+            loca: table(data, font_offset, b"loca")?,
+            glyf: table(data, font_offset, b"glyf")?,
+            long_offsets: read_i16(data, head + 50)? != 0,
+        })
+    }
+
+    /// Offset and format of the best supported character to glyph subtable:
+    /// full Unicode (format 12) over the basic multilingual plane (format 4).
+    fn cmap_subtable(data: &[u8], cmap: usize) -> Option<(usize, u16)> {
+        let num_tables = read_u16(data, cmap + 2)? as usize;
+        let subtables: Vec<_> = (0..num_tables)
+            .filter_map(|idx| {
+                let record = cmap + 4 + idx * 8;
+                let platform = read_u16(data, record)?;
+                let encoding = read_u16(data, record + 2)?;
+                let offset = cmap + read_u32(data, record + 4)? as usize;
+                let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+                if unicode { Some((offset, read_u16(data, offset)?)) } else { None }
+            })
+            .collect();
+
+        subtables.iter().find(|&&(_, format)| format == 12)
+            .or_else(|| subtables.iter().find(|&&(_, format)| format == 4))
+            .cloned()
+    }
+
+    /// Offset of the pairs of the first horizontal format 0 subtable of the `kern` table.
+    fn kern_pairs(data: &[u8], kern: usize) -> Option<usize> {
+        let num_tables = read_u16(data, kern + 2)? as usize;
+        let mut subtable = kern + 4;
+        for _ in 0..num_tables {
+            let length = read_u16(data, subtable + 2)? as usize;
+            let coverage = read_u16(data, subtable + 4)?;
+            if coverage >> 8 == 0 && coverage & 1 == 1 {
+                return Some(subtable + 6);
+            }
+            subtable += length;
+        }
+        None
+    }
+
+    fn scale(&self, size: Real) -> Real {
+        size / (self.ascent - self.descent)
+    }
+
+    fn line_height(&self) -> Real {
+        self.ascent - self.descent + self.line_gap
+    }
+
+    /// Pen positions after each char of `text`, advanced the way fontstash does.
+    fn pen_positions<'t>(&'t self, scale: Real, letter_spacing: Real, text: &'t str) -> impl Iterator<Item = Real> + 't {
+        self.glyph_pens(scale, letter_spacing, text).map(|(_, _, next_pen)| next_pen)
+    }
+
+    /// Glyphs of `text` with the pen position they are drawn at and the one after them.
+    /// Like fontstash's `(int)` casts, the kerned and the advanced offsets add a half and
+    /// truncate toward zero, so a kerning of -2.5 pixels moves the pen by -2.
+    fn glyph_pens<'t>(&'t self, scale: Real, letter_spacing: Real, text: &'t str)
+        -> impl Iterator<Item = (Real, u16, Real)> + 't
+    {
+        let mut pen = 0.0;
+        let mut prev_glyph = None;
+        text.chars().map(move |ch| {
+            let glyph = self.glyph_index(ch);
+            if let Some(prev_glyph) = prev_glyph {
+                pen += (self.kerning(prev_glyph, glyph) * scale + letter_spacing + 0.5).trunc();
+            }
+            let glyph_pen = pen;
+            pen += (self.advance(glyph) * scale + 0.5).trunc();
+            prev_glyph = Some(glyph);
+            (glyph_pen, glyph, pen)
+        })
+    }
+
+    /// Horizontal extent of the quad fontstash draws for `glyph` relative to its pen position:
+    /// the pixel aligned glyph box padded by one pixel. Empty glyphs still span the padding.
+    fn quad_extent(&self, glyph: u16, scale: Real) -> (Real, Real) {
+        let (x_min, x_max) = match self.glyf {
+            Some(_) => self.glyph_box(glyph).unwrap_or((0.0, 0.0)),
+            None => return (0.0, (self.advance(glyph) * scale + 0.5).trunc()),
+        };
+        ((x_min * scale).floor() - 1.0, (x_max * scale).ceil() + 1.0)
+    }
+
+    /// `xMin` and `xMax` of the `glyf` header of `glyph`, `None` for glyphs without outlines.
+    fn glyph_box(&self, glyph: u16) -> Option<(Real, Real)> {
+        let outlines = self.glyf?;
+        let data = &self.data;
+        let location = |idx: usize| if outlines.long_offsets {
+            read_u32(data, outlines.loca + idx * 4).map(|offset| offset as usize)
+        } else {
+            read_u16(data, outlines.loca + idx * 2).map(|offset| offset as usize * 2)
+        };
+
+        let start = location(glyph as usize)?;
+        if location(glyph as usize + 1)? <= start {
+            return None;
+        }
+        let header = outlines.glyf + start;
+        Some((Real::from(read_i16(data, header + 2)?), Real::from(read_i16(data, header + 6)?)))
+    }
+
+    fn advance(&self, glyph: u16) -> Real {
+        self.advances.get(glyph as usize)
+            .or_else(|| self.advances.last())
+            .map_or(0.0, |&advance| Real::from(advance))
+    }
+
+    fn glyph_index(&self, ch: char) -> u16 {
+        let glyph = match self.cmap {
+            Some((subtable, 12)) => self.segmented_coverage_glyph(subtable, ch as u32),
+            Some((subtable, 4)) => self.segment_mapping_glyph(subtable, ch as u32),
+            _ => None,
+        };
+        glyph.unwrap_or(0)
+    }
+
+    /// Format 12 lookup: groups of sequential codes mapped to sequential glyphs.
+    fn segmented_coverage_glyph(&self, subtable: usize, code: u32) -> Option<u16> {
+        let data = &self.data;
+        let num_groups = read_u32(data, subtable + 12)? as usize;
+        for group in (0..num_groups).map(|idx| subtable + 16 + idx * 12) {
+            let start = read_u32(data, group)?;
+            let end = read_u32(data, group + 4)?;
+            if start <= code && code <= end {
+                return read_u32(data, group + 8).map(|glyph| (glyph + code - start) as u16);
+            }
+        }
+        None
+    }
+
+    /// Format 4 lookup: segments of the basic multilingual plane with a delta or a glyph array.
+    fn segment_mapping_glyph(&self, subtable: usize, code: u32) -> Option<u16> {
+        if code > 0xFFFF {
+            return None;
+        }
+        let data = &self.data;
+        let seg_count = read_u16(data, subtable + 6)? as usize / 2;
+        let end_codes = subtable + 14;
+        let start_codes = end_codes + seg_count * 2 + 2;
+        let deltas = start_codes + seg_count * 2;
+        let range_offsets = deltas + seg_count * 2;
+
+        let seg = (0..seg_count).find(|&seg| {
+            read_u16(data, end_codes + seg * 2).map_or(false, |end| code <= u32::from(end))
+        })?;
+        let start = u32::from(read_u16(data, start_codes + seg * 2)?);
+        if code < start {
+            return None;
+        }
+
+        let delta = read_u16(data, deltas + seg * 2)?;
+        let range_offset = read_u16(data, range_offsets + seg * 2)? as usize;
+        if range_offset == 0 {
+            return Some((code as u16).wrapping_add(delta));
+        }
+        let address = range_offsets + seg * 2 + range_offset + (code - start) as usize * 2;
+        match read_u16(data, address)? {
+            0 => None,
+            glyph => Some(glyph.wrapping_add(delta)),
+        }
+    }
+
+    fn kerning(&self, left: u16, right: u16) -> Real {
+        let pairs = match self.kern {
+            Some(pairs) => pairs,
+            None => return 0.0,
+        };
+        let data = &self.data;
+        let key = u32::from(left) << 16 | u32::from(right);
+        let num_pairs = read_u16(data, pairs).unwrap_or(0) as usize;
+
+        let (mut low, mut high) = (0, num_pairs);
+        while low < high {
+            let mid = (low + high) / 2;
+            let pair = pairs + 8 + mid * 6;
+            match read_u32(data, pair) {
+                Some(pair_key) if pair_key < key => low = mid + 1,
+                Some(pair_key) if pair_key > key => high = mid,
+                Some(_) => return read_i16(data, pair + 4).map_or(0.0, Real::from),
+                None => break,
+            }
+        }
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{FONT, ROBOTO, measurer, assert_close};
+
+    fn roboto() -> FontFile {
+        FontFile::parse(fs::read(ROBOTO).expect("Roboto must be readable"))
+            .expect("Roboto must be parsable")
+    }
+
+    fn push_u16(data: &mut Vec<u8>, value: u16) {
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn push_u32(data: &mut Vec<u8>, value: u32) {
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// A format 12 `cmap` subtable mapping 'A' and 'B' to glyphs 1 and 2,
+    /// followed by a `kern` table with the single pair (1, 2).
+    fn synthetic() -> FontFile {
+        let mut data = Vec::new();
+        push_u16(&mut data, 12);
+        push_u16(&mut data, 0);
+        push_u32(&mut data, 28);
+        push_u32(&mut data, 0);
+        push_u32(&mut data, 1);
+        push_u32(&mut data, 'A' as u32);
+        push_u32(&mut data, 'B' as u32);
+        push_u32(&mut data, 1);
+
+        let kern = data.len();
+        push_u16(&mut data, 0);
+        push_u16(&mut data, 1);
+        push_u16(&mut data, 0);
+        push_u16(&mut data, 6 + 8 + 6);
+        push_u16(&mut data, 0x0001);
+        push_u16(&mut data, 1);
+        push_u16(&mut data, 6);
+        push_u16(&mut data, 0);
+        push_u16(&mut data, 0);
+        push_u16(&mut data, 1);
+        push_u16(&mut data, 2);
+        push_u16(&mut data, -300i16 as u16);
+
+        FontFile {
+            ascent: 800.0,
+            descent: -200.0,
+            line_gap: 0.0,
+            advances: vec![500, 600, 700],
+            cmap: Some((0, 12)),
+            kern: FontFile::kern_pairs(&data, kern),
+            glyf: None,
+            data,
+        }
+    }
+
+    #[test]
+    fn reads_the_horizontal_header_and_metrics() {
+        let font = roboto();
+        assert_eq!((font.ascent, font.descent, font.line_gap), (2146.0, -555.0, 0.0));
+        assert_eq!(font.advances.len(), 1035);
+
+        let metrics = measurer().metrics(FONT, 20.0).expect("Roboto is loaded");
+        assert_close(metrics.ascender, 15.8904, "ascender");
+        assert_close(metrics.descender, -4.1096, "descender");
+        assert_close(metrics.line_height, 20.0, "line height");
+    }
+
+    #[test]
+    fn maps_chars_through_the_segment_mapping_cmap() {
+        let font = roboto();
+        assert_eq!(font.cmap.map(|(_, format)| format), Some(4));
+        let glyphs: Vec<_> = "Hello".chars().map(|ch| font.glyph_index(ch)).collect();
+        assert_eq!(glyphs, vec![43, 72, 79, 79, 82]);
+        assert_eq!(font.glyph_index('\u{10FFFF}'), 0);
+        assert_eq!(font.kern, None);
+    }
+
+    #[test]
+    fn maps_chars_through_the_segmented_coverage_cmap() {
+        let font = synthetic();
+        assert_eq!(font.glyph_index('A'), 1);
+        assert_eq!(font.glyph_index('B'), 2);
+        assert_eq!(font.glyph_index('C'), 0);
+    }
+
+    #[test]
+    fn kerns_the_pairs_of_the_kern_table() {
+        let font = synthetic();
+        assert_eq!(font.kerning(1, 2), -300.0);
+        assert_eq!(font.kerning(2, 1), 0.0);
+
+        let positions: Vec<_> = font.pen_positions(font.scale(10.0), 0.0, "AB").collect();
+        assert_eq!(positions, vec![6.0, 11.0]);
+        let spaced: Vec<_> = font.pen_positions(font.scale(10.0), 2.0, "AB").collect();
+        assert_eq!(spaced, vec![6.0, 13.0]);
+    }
+
+    #[test]
+    fn spans_the_advances_without_glyph_outlines() {
+        let mut font = FontFileMeasurer::new();
+        font.fonts.insert("Synthetic".to_string(), synthetic());
+        let (advance, bounds) = font.bounds("Synthetic", 10.0, 0.0, "AB").expect("the font is added");
+        assert_eq!(advance, 11.0);
+        assert_eq!((bounds.min_x, bounds.max_x), (0.0, 11.0));
+    }
+
+    #[test]
+    fn reads_the_glyph_boxes_of_the_glyf_table() {
+        let font = roboto();
+        assert_eq!(font.glyph_box(font.glyph_index('A')), Some((20.0, 1306.0)));
+        assert_eq!(font.glyph_box(font.glyph_index('g')), Some((100.0, 1021.0)));
+        assert_eq!(font.glyph_box(font.glyph_index(' ')), None);
+    }
+
+    #[test]
+    fn bounds_span_the_padded_glyph_quads() {
+        let (advance, bounds) = measurer().bounds(FONT, 24.0, 0.0, "Align").expect("Roboto is loaded");
+        assert_eq!(advance, 42.0);
+        assert_eq!((bounds.min_x, bounds.max_x), (-1.0, 43.0));
+
+        let (advance, bounds) = measurer().bounds(FONT, 20.0, 0.0, " ").expect("Roboto is loaded");
+        assert_eq!(advance, 4.0);
+        assert_eq!((bounds.min_x, bounds.max_x), (-1.0, 1.0));
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use exgui::{Real, Drawable, Text};
+use crate::{BoundingBox, NodePath};
+use crate::debug::ShapeKind;
+use crate::style::NodeStyle;
+
+/// A node drawn by the last draw pass with its bound moved by the scroll offsets
+/// and the viewport it was clipped to.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PaintedNode {
+    pub path: NodePath,
+    pub kind: ShapeKind,
+    pub bound: BoundingBox,
+    pub clip: Option<BoundingBox>,
+}
+
+impl PaintedNode {
+    /// Groups and shapeless nodes paint nothing and leave the points of their bound
+    /// to what is below.
+    pub(crate) fn contains(&self, (x, y): (Real, Real)) -> bool {
+        if matches!(self.kind, ShapeKind::Group | ShapeKind::None) {
+            return false;
+        }
+        let inside = |bound: &BoundingBox| {
+            bound.min_x <= x && x <= bound.max_x && bound.min_y <= y && y <= bound.max_y
+        };
+        inside(&self.bound) && self.clip.as_ref().map_or(true, inside)
+    }
+}
+
+/// A subtree of the overlay layer, drawn after the whole tree at the scroll offset and
+/// opacity of its position but outside of the viewports it is in.
+pub(crate) struct Overlay<'a> {
+    pub path: NodePath,
+    pub draw: &'a dyn Drawable,
+    pub text: Option<&'a Text>,
+    pub offset: (Real, Real),
+    pub alpha: Real,
+}
+
+pub(crate) fn z_index(styles: &HashMap<NodePath, NodeStyle>, path: &[usize]) -> i32 {
+    styles.get(path).and_then(|style| style.z_index).unwrap_or(0)
+}
+
+pub(crate) fn is_overlay(styles: &HashMap<NodePath, NodeStyle>, path: &[usize]) -> bool {
+    styles.get(path).and_then(|style| style.overlay).unwrap_or(false)
+}
+
+/// Indices of `count` children of the node at `path` in paint order: by z-index,
+/// siblings with the same z-index in tree order.
+pub(crate) fn paint_order(styles: &HashMap<NodePath, NodeStyle>, path: &mut NodePath, count: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..count).collect();
+    order.sort_by_key(|&idx| {
+        path.push(idx);
+        let z = z_index(styles, path);
+        path.pop();
+        z
+    });
+    order
+}
@@ -1,11 +1,42 @@
 extern crate nanovg;
 extern crate exgui;
+#[cfg(feature = "shaping")]
+extern crate unicode_bidi;
 
+#[cfg(test)]
+#[macro_use]
+#[path = "../tests/common/mod.rs"]
+mod common;
+mod cache;
+mod caret;
+mod debug;
+mod dpi;
+mod dump;
+mod font;
+mod font_file;
+mod layer;
+mod measure;
+mod path;
+mod scroll;
+mod shaping;
+mod snap;
+mod stats;
+mod style;
+#[cfg(feature = "system-fonts")]
+mod system_fonts;
+mod text;
+mod ttf;
+
+use std::borrow::Cow;
 use std::path::Path;
+use std::cell::{Cell, RefCell, Ref};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::time::Instant;
 use nanovg::{
     Context, ContextBuilder, Font as NanovgFont, CreateFontError, Frame,
     Color as NanovgColor, Gradient as NanovgGradient, Paint as NanovgPaint,
-    StrokeOptions, PathOptions, TextOptions, Alignment,
+    StrokeOptions, PathOptions, TextOptions, Alignment, Clip, Scissor,
     LineCap as NanovgLineCap, LineJoin as NanovgLineJoin, Transform as NanovgTransform,
 };
 use exgui::{
@@ -14,6 +45,32 @@ use exgui::{
 };
 use exgui::renderer::Renderer;
 
+pub use cache::{RecalcCache, NodePath};
+pub use debug::DebugOverlay;
+pub use dpi::DeviceScale;
+pub use dump::{LayoutNode, LayoutValue};
+pub use font::FontFamilies;
+pub use font_file::FontFileMeasurer;
+pub use measure::{FontMetrics, GlyphPosition, TextMeasurer};
+pub use scroll::ScrollExtent;
+pub use style::{
+    NodeStyle, Span, TextFlow, FontWeight, FontStyle, TextDecoration, TextOverflow, TextSelection,
+    Length, SizeConstraints, Anchor, AnchorPoint, PathUnits, ViewBox, Scroll, Scrollbars, Visibility,
+};
+pub use stats::{RenderStats, RenderPass, Profiler};
+#[cfg(feature = "system-fonts")]
+pub use system_fonts::{SystemFonts, SystemFace};
+
+use caret::WordGeometry;
+use debug::ShapeKind;
+use stats::ProfilerHook;
+use layer::{Overlay, PaintedNode};
+use measure::Measure;
+use path::{Segment, PathLayouts};
+use scroll::ScrollLayouts;
+use snap::PixelSnap;
+use text::{FlowWord, SpanSettings, TextRun, TextLayouts};
+
 struct ToNanovgPaint(Paint);
 
 impl ToNanovgPaint {
@@ -78,6 +135,61 @@ impl BoundingBox {
     pub fn height(&self) -> Real {
         self.max_y - self.min_y
     }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    pub fn translate(&self, (dx, dy): (Real, Real)) -> BoundingBox {
+        BoundingBox {
+            min_x: self.min_x + dx,
+            min_y: self.min_y + dy,
+            max_x: self.max_x + dx,
+            max_y: self.max_y + dy,
+        }
+    }
+
+    /// Empty intersections keep a zero size at the edge of `self`.
+    pub fn intersection(&self, other: &BoundingBox) -> BoundingBox {
+        let min_x = self.min_x.max(other.min_x);
+        let min_y = self.min_y.max(other.min_y);
+        BoundingBox {
+            min_x,
+            min_y,
+            max_x: self.max_x.min(other.max_x).max(min_x),
+            max_y: self.max_y.min(other.max_y).max(min_y),
+        }
+    }
+
+    pub fn transform(&self, matrix: &[Real; 6]) -> BoundingBox {
+        let [a, b, c, d, e, f] = *matrix;
+        let corners = [
+            (self.min_x, self.min_y),
+            (self.max_x, self.min_y),
+            (self.min_x, self.max_y),
+            (self.max_x, self.max_y),
+        ];
+
+        let mut bound = BoundingBox {
+            min_x: Real::INFINITY,
+            min_y: Real::INFINITY,
+            max_x: Real::NEG_INFINITY,
+            max_y: Real::NEG_INFINITY,
+        };
+        for &(x, y) in corners.iter() {
+            let (tx, ty) = (a * x + c * y + e, b * x + d * y + f);
+            bound.min_x = bound.min_x.min(tx);
+            bound.min_y = bound.min_y.min(ty);
+            bound.max_x = bound.max_x.max(tx);
+            bound.max_y = bound.max_y.max(ty);
+        }
+        bound
+    }
 }
 
 #[derive(Debug)]
@@ -85,14 +197,83 @@ pub enum NanovgRendererError {
     ContextIsNotInit,
     InitNanovgContextFailed,
     CreateFontError(CreateFontError, String),
+    FontNotFound(String),
+    WordNotFound(NodePath),
+}
+
+/// Per-node results of the recalc pass that the draw pass reads.
+#[derive(Debug, Default)]
+struct Layouts {
+    text: TextLayouts,
+    paths: PathLayouts,
+    scrolls: ScrollLayouts,
+    bounds: HashMap<NodePath, BoundingBox>,
+    /// Translation of the children of anchored auto sized shapes: their children are
+    /// laid out before the anchor moves the shape, and are drawn moved along with it.
+    shifts: HashMap<NodePath, (Real, Real)>,
 }
 
+impl Layouts {
+    fn clear(&mut self) {
+        self.text.clear();
+        self.paths.clear();
+        self.scrolls.clear();
+        self.bounds.clear();
+        self.shifts.clear();
+    }
+
+    /// Drops the results of nodes that are no longer in the tree.
+    fn retain(&mut self, cache: &RecalcCache) {
+        self.text.retain(|path, _| cache.contains(path));
+        self.paths.retain(|path, _| cache.contains(path));
+        self.scrolls.retain(|path, _| cache.contains(path));
+        self.bounds.retain(|path, _| cache.contains(path));
+        self.shifts.retain(|path, _| cache.contains(path));
+    }
+
+    /// Sum of the shifts of the ancestors of `path`.
+    fn shift(&self, path: &[usize]) -> (Real, Real) {
+        (0..path.len())
+            .filter_map(|len| self.shifts.get(&path[..len]))
+            .fold((0.0, 0.0), |(x, y), &(dx, dy)| (x + dx, y + dy))
+    }
+
+    /// Node bounds where the nodes are drawn, with the shifts of their ancestors.
+    fn placed_bounds(&self) -> Cow<HashMap<NodePath, BoundingBox>> {
+        if self.shifts.is_empty() {
+            return Cow::Borrowed(&self.bounds);
+        }
+        Cow::Owned(
+            self.bounds.iter()
+                .map(|(path, bound)| (path.clone(), bound.translate(self.shift(path))))
+                .collect()
+        )
+    }
+}
+
+/// Layout coordinates, font sizes and stroke widths are in logical pixels; the frame
+/// is `width` by `height` logical pixels and `device_pixel_ratio` physical pixels
+/// make one logical pixel.
 #[derive(Debug, Default)]
 pub struct NanovgRenderer {
     pub context: Option<Context>,
     pub width: f32,
     pub height: f32,
     pub device_pixel_ratio: f32,
+    laid_out_scale: Cell<Option<DeviceScale>>,
+    pub recalc_cache: Option<RefCell<RecalcCache>>,
+    styles: HashMap<NodePath, NodeStyle>,
+    fonts: RefCell<FontFamilies>,
+    #[cfg(feature = "system-fonts")]
+    system_fonts: Option<RefCell<SystemFonts>>,
+    #[cfg(feature = "system-fonts")]
+    checked_fonts: RefCell<HashSet<String>>,
+    layouts: RefCell<Layouts>,
+    painted: RefCell<Vec<PaintedNode>>,
+    stats: Option<Cell<RenderStats>>,
+    profiler: Option<RefCell<ProfilerHook>>,
+    debug_overlay: Option<DebugOverlay>,
+    pixel_snapping: bool,
 }
 
 impl Renderer for NanovgRenderer {
@@ -110,21 +291,58 @@ impl Renderer for NanovgRenderer {
     }
 
     fn render(&self, node: &mut dyn Drawable) -> Result<(), Self::Error> {
+        let scale = self.scale();
+        if self.laid_out_scale.replace(Some(scale)).map_or(false, |laid_out| laid_out != scale) {
+            // Text is measured at the device pixel ratio of the frame
+            self.invalidate_layout();
+        }
         self.context
             .as_ref()
             .ok_or(NanovgRendererError::ContextIsNotInit)?
             .frame(
                 (self.width, self.height),
-                self.device_pixel_ratio,
+                scale.ratio(),
                 move |frame| {
-                    let bound = BoundingBox {
-                        min_x: 0.0,
-                        min_y: 0.0,
-                        max_x: self.width,
-                        max_y: self.height,
-                    };
-                    Self::render_recalc(&frame, node, bound, None);
-                    Self::render_draw(&frame, node, None);
+                    let mut layouts = self.layouts.borrow_mut();
+                    let mut profiler = self.profiler.as_ref().map(|hook| hook.borrow_mut());
+                    let mut font_names = HashSet::new();
+                    let mut stats = self.recalc(
+                        &frame,
+                        node,
+                        &mut layouts,
+                        profiler.as_mut().map(|hook| hook.profiler()),
+                        Some(&mut font_names),
+                    );
+                    if self.load_missing_fonts(&font_names) {
+                        // The text in the fonts loaded just now was laid out as missing
+                        if let Some(ref cache) = self.recalc_cache {
+                            cache.borrow_mut().clear();
+                        }
+                        layouts.clear();
+                        let profiler = profiler.as_mut().map(|hook| hook.profiler());
+                        stats = self.recalc(&frame, node, &mut layouts, profiler, None);
+                    }
+                    let fonts = self.fonts.borrow();
+
+                    let draw_start = Instant::now();
+                    let mut painted = self.painted.borrow_mut();
+                    painted.clear();
+                    let snap = if self.pixel_snapping { Some(PixelSnap::new(scale)) } else { None };
+                    let mut stats = Draw::new(&frame, &self.styles, &fonts, &layouts)
+                        .with_profiler(profiler.as_mut().map(|hook| hook.profiler()))
+                        .with_painted(Some(&mut painted))
+                        .with_pixel_snap(snap)
+                        .run(node, stats);
+                    stats.draw_time = draw_start.elapsed();
+
+                    if let Some(overlay) = self.debug_overlay.as_ref() {
+                        let node_bounds = debug::node_bounds(&painted);
+                        debug::draw_overlay(&frame, overlay, &node_bounds);
+                    }
+
+                    if let Some(ref last_stats) = self.stats {
+                        last_stats.set(stats);
+                    }
                 }
             );
         Ok(())
@@ -135,9 +353,7 @@ impl NanovgRenderer {
     pub fn new_with_context(context: Context) -> Self {
         Self {
             context: Some(context),
-            width: 0.0,
-            height: 0.0,
-            device_pixel_ratio: 0.0,
+            ..Default::default()
         }
     }
 
@@ -156,33 +372,798 @@ impl NanovgRenderer {
         self
     }
 
+    pub fn with_recalc_cache(mut self) -> Self {
+        self.recalc_cache = Some(RefCell::new(RecalcCache::new()));
+        self
+    }
+
+    /// Collects `RenderStats` of each frame, see `last_stats`.
+    pub fn with_stats(mut self) -> Self {
+        self.stats = Some(Cell::new(RenderStats::default()));
+        self
+    }
+
+    pub fn with_profiler<P: Profiler + 'static>(mut self, profiler: P) -> Self {
+        self.profiler = Some(RefCell::new(ProfilerHook(Box::new(profiler))));
+        self
+    }
+
+    pub fn last_stats(&self) -> Option<RenderStats> {
+        self.stats.as_ref().map(|stats| stats.get())
+    }
+
+    pub fn with_debug_overlay(mut self, overlay: DebugOverlay) -> Self {
+        self.debug_overlay = Some(overlay);
+        self
+    }
+
+    pub fn set_debug_overlay(&mut self, overlay: Option<DebugOverlay>) {
+        self.debug_overlay = overlay;
+    }
+
+    /// Draws untransformed rects, circles and paths and their strokes on whole device pixels
+    /// for crisp edges, e.g. of 1px lines at a fractional `device_pixel_ratio`.
+    pub fn with_pixel_snapping(mut self) -> Self {
+        self.pixel_snapping = true;
+        self
+    }
+
+    pub fn set_pixel_snapping(&mut self, pixel_snapping: bool) {
+        self.pixel_snapping = pixel_snapping;
+    }
+
+    pub fn find_path<F>(draw: &dyn Drawable, predicate: F) -> Option<NodePath>
+    where
+        F: Fn(&Shape) -> bool,
+    {
+        fn find<F: Fn(&Shape) -> bool>(draw: &dyn Drawable, predicate: &F, path: &mut NodePath) -> bool {
+            if draw.shape().map_or(false, |shape| predicate(shape)) {
+                return true;
+            }
+            if let Some(childs) = draw.childs() {
+                for (idx, child) in childs.into_iter().enumerate() {
+                    path.push(idx);
+                    if find(child, predicate, path) {
+                        return true;
+                    }
+                    path.pop();
+                }
+            }
+            false
+        }
+
+        let mut path = Vec::new();
+        if find(draw, &predicate, &mut path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    pub fn style(&self, path: &NodePath) -> Option<&NodeStyle> {
+        self.styles.get(path)
+    }
+
+    /// Merges `style` into the style of the node at `path`: the properties set in `style`
+    /// replace the current ones and the unset ones are kept, `remove_style` resets them all.
+    /// Styles stay at `path` when the tree changes, see `NodeStyle`.
+    pub fn set_style(&mut self, path: NodePath, style: NodeStyle) {
+        let current = self.styles.entry(path.clone()).or_insert_with(NodeStyle::default);
+        let previous = current.clone();
+        current.merge(style);
+        if !current.same_layout(&previous) {
+            self.invalidate_path(&path);
+        }
+    }
+
+    /// Shows or hides the node at `path` without rebuilding the tree. Only collapsing
+    /// and expanding change the layout and invalidate it.
+    pub fn set_visibility(&mut self, path: NodePath, visibility: Visibility) {
+        let style = self.styles.entry(path.clone()).or_insert_with(NodeStyle::default);
+        let collapsed = style.visibility == Some(Visibility::Collapsed);
+        let relayout = collapsed != (visibility == Visibility::Collapsed);
+        style.visibility = Some(visibility);
+        if relayout {
+            self.invalidate_path(&path);
+        }
+    }
+
+    /// Sets the opacity of the node at `path` and its subtree, it needs no recalc.
+    pub fn set_opacity(&mut self, path: NodePath, opacity: Real) {
+        self.styles.entry(path).or_insert_with(NodeStyle::default).opacity = Some(opacity);
+    }
+
+    pub fn remove_style(&mut self, path: &NodePath) -> Option<NodeStyle> {
+        let style = self.styles.remove(path);
+        if style.as_ref().map_or(false, |style| !style.same_layout(&NodeStyle::default())) {
+            self.invalidate_path(path);
+        }
+        style
+    }
+
+    /// Sets the offset of the `Scroll` of the node at `path`, clamped to its last extent.
+    /// Scrolling keeps the layout, so it needs no recalc.
+    pub fn scroll_to(&mut self, path: &NodePath, offset: (Real, Real)) {
+        let offset = self.scroll_extent(path).map_or(offset, |extent| extent.clamp(offset));
+        if let Some(scroll) = self.styles.get_mut(path).and_then(|style| style.scroll.as_mut()) {
+            scroll.offset = offset;
+        }
+    }
+
+    /// Viewport and content bounds of the scrolled node at `path` after the last recalc.
+    pub fn scroll_extent(&self, path: &NodePath) -> Option<ScrollExtent> {
+        self.layouts.borrow().scrolls.get(path).cloned()
+    }
+
+    /// Path of the topmost node drawn at `point` by the last render, the deepest one among
+    /// nested nodes. It follows the paint order with z-indices, overlays, scroll offsets
+    /// and viewport clips.
+    pub fn hit_test(&self, point: (Real, Real)) -> Option<NodePath> {
+        self.painted.borrow().iter().rev()
+            .find(|node| node.contains(point))
+            .map(|node| node.path.clone())
+    }
+
+    /// Lays out the node at `path` again at the next render, with what depends on its style.
+    /// The results of the other nodes stay in the `RecalcCache`, and a render without
+    /// the cache lays out everything anyway.
+    fn invalidate_path(&self, path: &[usize]) {
+        if let Some(ref cache) = self.recalc_cache {
+            cache.borrow_mut().invalidate(path);
+        }
+    }
+
+    fn invalidate_layout(&self) {
+        if let Some(ref cache) = self.recalc_cache {
+            cache.borrow_mut().clear();
+        }
+        self.layouts.borrow_mut().clear();
+    }
+
+    /// Sets the frame to `dimensions` logical pixels at `device_pixel_ratio`. Windows
+    /// that report their inner size in physical pixels use `set_physical_dimensions`.
     pub fn set_dimensions(&mut self, (width, height): (u32, u32), device_pixel_ratio: f32) {
-        self.width = width as f32;
-        self.height = height as f32;
+        self.set_logical_dimensions((width as Real, height as Real), device_pixel_ratio);
+    }
+
+    /// Sets the frame to `dimensions` physical pixels, e.g. the inner size of the window,
+    /// at `device_pixel_ratio` physical pixels per logical pixel.
+    pub fn set_physical_dimensions(&mut self, dimensions: (u32, u32), device_pixel_ratio: f32) {
+        let (width, height) = DeviceScale::new(device_pixel_ratio).to_logical_size(dimensions);
+        self.set_logical_dimensions((width, height), device_pixel_ratio);
+    }
+
+    pub fn set_logical_dimensions(&mut self, (width, height): (Real, Real), device_pixel_ratio: f32) {
+        self.width = width;
+        self.height = height;
+        self.device_pixel_ratio = device_pixel_ratio;
+    }
+
+    /// Changes the ratio at runtime, e.g. when the window moves to another monitor,
+    /// keeping the logical dimensions. The next render lays out the tree again.
+    pub fn set_device_pixel_ratio(&mut self, device_pixel_ratio: f32) {
         self.device_pixel_ratio = device_pixel_ratio;
     }
 
-    pub fn load_font<S, P>(&mut self, name: S, path: P) -> Result<(), <Self as Renderer>::Error>
-    where
-        S: Into<String>,
-        P: AsRef<Path>,
+    /// Conversions between logical and physical pixels at the current `device_pixel_ratio`,
+    /// e.g. for components that align to device pixels or draw hairlines.
+    pub fn scale(&self) -> DeviceScale {
+        DeviceScale::new(self.device_pixel_ratio)
+    }
+
+    pub fn logical_dimensions(&self) -> (Real, Real) {
+        (self.width, self.height)
+    }
+
+    pub fn physical_dimensions(&self) -> (u32, u32) {
+        self.scale().to_physical_size((self.width, self.height))
+    }
+
+    pub fn load_font<S, P>(&mut self, name: S, path: P) -> Result<(), <Self as Renderer>::Error>
+    where
+        S: Into<String>,
+        P: AsRef<Path>,
+    {
+        let name = name.into();
+        let display_path = path.as_ref().display();
+        NanovgFont::from_file(
+            self.context.as_ref().ok_or(NanovgRendererError::ContextIsNotInit)?,
+            name.as_str(),
+            path.as_ref()
+        ).map_err(|e| NanovgRendererError::CreateFontError(e, format!("{}", display_path)))?;
+        Ok(())
+    }
+
+    /// Loads a face of the `family` font family. `Text::font_name` and `Span::font_name`
+    /// may then name the family and get the closest loaded face for their weight and style.
+    pub fn load_font_face<S, P>(&mut self,
+                                family: S,
+                                path: P,
+                                weight: FontWeight,
+                                style: FontStyle) -> Result<(), <Self as Renderer>::Error>
+    where
+        S: Into<String>,
+        P: AsRef<Path>,
+    {
+        let family = family.into();
+        let font_name = format!("{}:{}:{:?}", family, weight.value(), style);
+        self.load_font(font_name.as_str(), path)?;
+        self.add_font_face(&family, &font_name, weight, style);
+        Ok(())
+    }
+
+    /// Registers an already loaded font as a face of the `family` font family.
+    pub fn add_font_face(&mut self, family: &str, font_name: &str, weight: FontWeight, style: FontStyle) {
+        self.invalidate_layout();
+        self.fonts.borrow_mut().add_face(family, font_name, weight, style);
+    }
+
+    pub fn fonts(&self) -> Ref<FontFamilies> {
+        self.fonts.borrow()
+    }
+
+    #[cfg(feature = "system-fonts")]
+    pub fn with_system_fonts(mut self, system_fonts: SystemFonts) -> Self {
+        self.system_fonts = Some(RefCell::new(system_fonts));
+        self.checked_fonts.borrow_mut().clear();
+        // Lays out every text again, so the recalc pass meets all the font names in use
+        self.invalidate_layout();
+        self
+    }
+
+    /// Loads the system fonts of the `font_names` the recalc pass met and of the spans.
+    /// Every font name is looked up once. `true` when a font was loaded, so the text laid
+    /// out in it as missing must be laid out again.
+    #[cfg(feature = "system-fonts")]
+    fn load_missing_fonts(&self, font_names: &HashSet<String>) -> bool {
+        if self.context.is_none() || self.system_fonts.is_none() {
+            return false;
+        }
+        let span_fonts = self.styles.values().filter_map(|style| style.span.as_ref()?.font_name.as_ref());
+        let mut loaded = false;
+        for name in font_names.iter().chain(span_fonts) {
+            let unchecked = self.checked_fonts.borrow_mut().insert(name.clone());
+            if unchecked {
+                loaded |= self.load_system_font(name);
+            }
+        }
+        loaded
+    }
+
+    #[cfg(not(feature = "system-fonts"))]
+    fn load_missing_fonts(&self, _font_names: &HashSet<String>) -> bool {
+        false
+    }
+
+    /// Loads `name` from the system fonts if it is neither a loaded font nor a family.
+    #[cfg(feature = "system-fonts")]
+    fn load_system_font(&self, name: &str) -> bool {
+        use system_fonts::SystemMatch;
+
+        let (context, system_fonts) = match (self.context.as_ref(), self.system_fonts.as_ref()) {
+            (Some(context), Some(system_fonts)) => (context, system_fonts),
+            _ => return false,
+        };
+        if self.fonts.borrow().contains(name) || NanovgFont::find(context, name).is_ok() {
+            return false;
+        }
+
+        match system_fonts.borrow_mut().find(name) {
+            Some(SystemMatch::Family(faces)) => {
+                let mut fonts = self.fonts.borrow_mut();
+                for face in faces {
+                    let font_name = format!("{}:{}:{:?}", face.family, face.weight.value(), face.style);
+                    if NanovgFont::from_file(context, font_name.as_str(), &face.path).is_ok() {
+                        fonts.add_face(name, &font_name, face.weight, face.style);
+                    }
+                }
+                fonts.contains(name)
+            },
+            Some(SystemMatch::Face(face)) => NanovgFont::from_file(context, name, &face.path).is_ok(),
+            None => false,
+        }
+    }
+
+    #[cfg(not(feature = "system-fonts"))]
+    fn load_system_font(&self, _name: &str) -> bool {
+        false
+    }
+
+    pub fn measure_text(&self,
+                        font_name: &str,
+                        font_size: Real,
+                        align: (AlignHor, AlignVer),
+                        text: &str) -> Result<BoundingBox, <Self as Renderer>::Error>
+    {
+        self.with_frame(|frame| self.measure_text_with(frame, font_name, font_size, align, text))?
+    }
+
+    /// Same as `measure_text` but measures with `measurer`.
+    pub fn measure_text_with(&self,
+                             measurer: &dyn TextMeasurer,
+                             font_name: &str,
+                             font_size: Real,
+                             (align_hor, align_ver): (AlignHor, AlignVer),
+                             text: &str) -> Result<BoundingBox, <Self as Renderer>::Error>
+    {
+        self.with_measure(measurer, font_name, font_size, |measure| {
+            measure.aligned_bounds((0.0, 0.0), text, &align_hor, &align_ver)
+        })
+    }
+
+    pub fn font_metrics(&self, font_name: &str, font_size: Real) -> Result<FontMetrics, <Self as Renderer>::Error> {
+        self.with_frame(|frame| self.font_metrics_with(frame, font_name, font_size))?
+    }
+
+    /// Same as `font_metrics` but measures with `measurer`.
+    pub fn font_metrics_with(&self,
+                             measurer: &dyn TextMeasurer,
+                             font_name: &str,
+                             font_size: Real) -> Result<FontMetrics, <Self as Renderer>::Error>
+    {
+        self.with_measure(measurer, font_name, font_size, |measure| {
+            measure.metrics()
+        })
+    }
+
+    pub fn glyph_positions(&self,
+                           font_name: &str,
+                           font_size: Real,
+                           align_hor: AlignHor,
+                           text: &str) -> Result<Vec<GlyphPosition>, <Self as Renderer>::Error>
+    {
+        self.with_frame(|frame| self.glyph_positions_with(frame, font_name, font_size, align_hor, text))?
+    }
+
+    /// Same as `glyph_positions` but measures with `measurer`.
+    pub fn glyph_positions_with(&self,
+                                measurer: &dyn TextMeasurer,
+                                font_name: &str,
+                                font_size: Real,
+                                align_hor: AlignHor,
+                                text: &str) -> Result<Vec<GlyphPosition>, <Self as Renderer>::Error>
+    {
+        self.with_measure(measurer, font_name, font_size, |measure| {
+            measure.glyph_positions(0.0, text, &align_hor)
+        })
+    }
+
+    /// Glyph positions of the `Word` at `word_path` in `root` as the last recalc laid it out,
+    /// in the coordinates of its `Text` before the `Text` transform.
+    pub fn word_glyph_positions(&self,
+                                root: &dyn Drawable,
+                                word_path: &NodePath) -> Result<Vec<GlyphPosition>, <Self as Renderer>::Error>
+    {
+        self.with_word_geometry(root, word_path, |geometry, _, _| geometry.glyphs())
+    }
+
+    /// Caret before the byte `index` of the `Word` at `word_path` in `root`, on the line
+    /// the last recalc laid the index out on, or `None` if the word is on no line.
+    pub fn caret_rect(&self,
+                      root: &dyn Drawable,
+                      word_path: &NodePath,
+                      index: usize,
+                      width: Real) -> Result<Option<BoundingBox>, <Self as Renderer>::Error>
+    {
+        self.with_word_geometry(root, word_path, |geometry, transform, shift| {
+            geometry.caret(index, width).map(|rect| Self::transformed(rect, transform, shift))
+        })
+    }
+
+    /// Rects of the byte range of the `Word` at `word_path` in `root`, one per line.
+    pub fn selection_rects(&self,
+                           root: &dyn Drawable,
+                           word_path: &NodePath,
+                           (start, end): (usize, usize)) -> Result<Vec<BoundingBox>, <Self as Renderer>::Error>
+    {
+        self.with_word_geometry(root, word_path, |geometry, transform, shift| {
+            geometry.selection(start, end).into_iter().map(|rect| Self::transformed(rect, transform, shift)).collect()
+        })
+    }
+
+    /// Byte index of the caret position of the `Word` at `word_path` in `root` nearest
+    /// to `point`, on the line nearest to it.
+    pub fn index_at(&self,
+                    root: &dyn Drawable,
+                    word_path: &NodePath,
+                    point: (Real, Real)) -> Result<usize, <Self as Renderer>::Error>
+    {
+        self.with_word_geometry(root, word_path, |geometry, transform, (shift_x, shift_y)| {
+            let point = (point.0 - shift_x, point.1 - shift_y);
+            let point = transform
+                .and_then(|transform| path::invert(&transform.matrix))
+                .map_or(point, |inverse| path::apply(&inverse, point));
+            geometry.index_at(point)
+        })
+    }
+
+    fn transformed(rect: BoundingBox, transform: Option<&Transform>, shift: (Real, Real)) -> BoundingBox {
+        transform.map_or(rect, |transform| rect.transform(&transform.matrix)).translate(shift)
+    }
+
+    fn node_at<'d>(draw: &'d dyn Drawable, path: &[usize]) -> Option<&'d dyn Drawable> {
+        match path.split_first() {
+            Some((&idx, rest)) => Self::node_at(draw.childs()?.into_iter().nth(idx)?, rest),
+            None => Some(draw),
+        }
+    }
+
+    /// Geometry of the `Word` at `word_path` with the spans of its style and the runs
+    /// of the last recalc, passed on with the transform of its `Text` and the shift
+    /// of its anchored ancestors.
+    fn with_word_geometry<T, F>(&self,
+                                root: &dyn Drawable,
+                                word_path: &NodePath,
+                                geometry: F) -> Result<T, <Self as Renderer>::Error>
+    where
+        F: FnOnce(WordGeometry, Option<&Transform>, (Real, Real)) -> T,
+    {
+        let not_found = || NanovgRendererError::WordNotFound(word_path.clone());
+        let (_, text_path) = word_path.split_last().ok_or_else(not_found)?;
+        let text = match Self::node_at(root, text_path).and_then(|node| node.shape()) {
+            Some(Shape::Text(text)) => text,
+            _ => return Err(not_found()),
+        };
+        let content = match Self::node_at(root, word_path).and_then(|node| node.shape()) {
+            Some(Shape::Word(word)) => shaping::shaped(word.as_ref()),
+            _ => return Err(not_found()),
+        };
+
+        if self.load_system_font(&text.font_name) {
+            self.invalidate_layout();
+        }
+        let spans = style::word_spans(&self.styles, word_path);
+        let fonts = self.fonts.borrow();
+        let settings = SpanSettings::new(&fonts, text, &spans);
+        let layouts = self.layouts.borrow();
+        let runs = layouts.text.get(word_path).map(Vec::as_slice);
+        let shift = layouts.shift(word_path);
+
+        self.with_frame(|frame| {
+            if settings.measure(frame).has_font() {
+                let word_geometry = WordGeometry::of_word(frame, text, &settings, &content, runs);
+                Ok(geometry(word_geometry, text.transform.as_ref(), shift))
+            } else {
+                Err(NanovgRendererError::FontNotFound(settings.font_name.to_string()))
+            }
+        })?
+    }
+
+    fn with_measure<T, F>(&self,
+                          measurer: &dyn TextMeasurer,
+                          font_name: &str,
+                          font_size: Real,
+                          measure: F) -> Result<T, <Self as Renderer>::Error>
+    where
+        F: FnOnce(&Measure) -> T,
+    {
+        if self.load_system_font(font_name) {
+            self.invalidate_layout();
+        }
+
+        let fonts = self.fonts.borrow();
+        let font_name = fonts.resolve(font_name, FontWeight::Normal, FontStyle::Normal).font_name;
+        let font_measure = Measure::new(measurer, font_name, font_size);
+        if font_measure.has_font() {
+            Ok(measure(&font_measure))
+        } else {
+            Err(NanovgRendererError::FontNotFound(font_name.to_string()))
+        }
+    }
+
+    /// Text measurement needs a frame; an empty frame does not draw anything
+    fn with_frame<T, F>(&self, frame_fn: F) -> Result<T, <Self as Renderer>::Error>
+    where
+        F: FnOnce(&Frame) -> T,
+    {
+        let context = self.context.as_ref().ok_or(NanovgRendererError::ContextIsNotInit)?;
+        let mut result = None;
+        context.frame((self.width, self.height), self.scale().ratio(), |frame| {
+            result = Some(frame_fn(&frame));
+        });
+        result.ok_or(NanovgRendererError::ContextIsNotInit)
+    }
+
+    /// Runs the recalc pass on `node` without drawing and returns the resolved tree,
+    /// e.g. to compare `LayoutNode::to_text` or `LayoutNode::to_json` with a snapshot.
+    pub fn layout(&self, node: &mut dyn Drawable) -> Result<LayoutNode, <Self as Renderer>::Error> {
+        self.with_frame(|frame| {
+            let mut font_names = HashSet::new();
+            let layout = self.layout_tree(frame, node, Some(&mut font_names));
+            if self.load_missing_fonts(&font_names) {
+                self.invalidate_layout();
+                return self.layout_tree(frame, node, None);
+            }
+            layout
+        })
+    }
+
+    /// Same as `layout` but measures text with `measurer`.
+    pub fn layout_with(&self, measurer: &dyn TextMeasurer, node: &mut dyn Drawable) -> LayoutNode {
+        self.layout_tree(measurer, node, None)
+    }
+
+    fn layout_tree(&self,
+                   measurer: &dyn TextMeasurer,
+                   node: &mut dyn Drawable,
+                   font_names: Option<&mut HashSet<String>>) -> LayoutNode
+    {
+        let fonts = self.fonts.borrow();
+        let bound = BoundingBox {
+            min_x: 0.0,
+            min_y: 0.0,
+            max_x: self.width,
+            max_y: self.height,
+        };
+
+        let mut layouts = Layouts::default();
+        Recalc::new(measurer, None, &self.styles, &fonts, &mut layouts)
+            .with_font_names(font_names)
+            .run(node, bound);
+        dump::layout_tree(node, &layouts.placed_bounds(), &layouts.text)
+    }
+
+    /// Runs the recalc pass of `render` on `node`, with the recalc cache when it is enabled,
+    /// but measures text with `measurer` and draws nothing, e.g. to benchmark the layout.
+    pub fn recalc_with(&self, measurer: &dyn TextMeasurer, node: &mut dyn Drawable) -> RenderStats {
+        let mut layouts = self.layouts.borrow_mut();
+        let mut profiler = self.profiler.as_ref().map(|hook| hook.borrow_mut());
+        self.recalc(measurer, node, &mut layouts, profiler.as_mut().map(|hook| hook.profiler()), None)
+    }
+
+    fn recalc(&self,
+              measurer: &dyn TextMeasurer,
+              node: &mut dyn Drawable,
+              layouts: &mut Layouts,
+              profiler: Option<&mut dyn Profiler>,
+              font_names: Option<&mut HashSet<String>>) -> RenderStats
+    {
+        let fonts = self.fonts.borrow();
+        let bound = BoundingBox {
+            min_x: 0.0,
+            min_y: 0.0,
+            max_x: self.width,
+            max_y: self.height,
+        };
+
+        let measurements = measure::measurement_count();
+        let recalc_start = Instant::now();
+        let mut stats = match self.recalc_cache {
+            Some(ref cache) => {
+                let mut cache = cache.borrow_mut();
+                cache.begin_frame(node);
+                let stats = Recalc::new(measurer, Some(&mut *cache), &self.styles, &fonts, layouts)
+                    .with_profiler(profiler)
+                    .with_font_names(font_names)
+                    .run(node, bound);
+                cache.end_frame();
+                layouts.retain(&cache);
+                stats
+            },
+            None => {
+                layouts.clear();
+                Recalc::new(measurer, None, &self.styles, &fonts, layouts)
+                    .with_profiler(profiler)
+                    .with_font_names(font_names)
+                    .run(node, bound)
+            },
+        };
+        stats.recalc_time = recalc_start.elapsed();
+        stats.text_measurements = measure::measurement_count() - measurements;
+        stats
+    }
+
+    /// Recalculates the layout of `draw`. A nanovg `Frame` or any other `TextMeasurer`,
+    /// such as `FontFileMeasurer`, measures the text.
+    pub fn render_recalc(measurer: &dyn TextMeasurer,
+                         draw: &mut dyn Drawable,
+                         parent_bound: BoundingBox,
+                         text: Option<&Text>) -> BoundingBox
+    {
+        Recalc::new(measurer, None, &HashMap::new(), &FontFamilies::new(), &mut Layouts::default())
+            .node(draw, parent_bound, text)
+    }
+
+    fn to_nanovg_transform(transform: Option<&Transform>) -> Option<NanovgTransform> {
+        transform.map(|transform| {
+            let mut nanovg_transform = NanovgTransform::new();
+            if transform.absolute {
+                nanovg_transform.absolute();
+            }
+            nanovg_transform.matrix = transform.matrix;
+            nanovg_transform
+        })
+    }
+
+    /// Text transform with the synthetic oblique skew around the baseline at `oblique_baseline`.
+    fn run_transform(transform: Option<&Transform>, oblique_baseline: Option<Real>) -> Option<NanovgTransform> {
+        let baseline = match oblique_baseline {
+            Some(baseline) => baseline,
+            None => return Self::to_nanovg_transform(transform),
+        };
+
+        let skew = [1.0, 0.0, -text::OBLIQUE_SKEW, 1.0, text::OBLIQUE_SKEW * baseline, 0.0];
+        let mut nanovg_transform = NanovgTransform::new();
+        nanovg_transform.matrix = match transform {
+            Some(transform) => {
+                if transform.absolute {
+                    nanovg_transform.absolute();
+                }
+                path::multiply(&transform.matrix, &skew)
+            },
+            None => skew,
+        };
+        Some(nanovg_transform)
+    }
+
+    fn path_options(transform: Option<&Transform>) -> PathOptions {
+        if let Some(transform) = transform {
+            let mut nanovg_transform = NanovgTransform::new();
+            if transform.absolute {
+                nanovg_transform.absolute();
+            }
+            nanovg_transform.matrix = transform.matrix;
+            PathOptions {
+                transform: Some(nanovg_transform),
+                ..Default::default()
+            }
+        } else {
+            PathOptions::default()
+        }
+    }
+
+    /// A circle fits the constrained box of its diameter.
+    fn constrained_radius(constraints: &SizeConstraints, r: Real, parent_size: (Real, Real)) -> Real {
+        let (width, height) = constraints.apply((r * 2.0, r * 2.0), parent_size, (true, true));
+        width.min(height) / 2.0
+    }
+
+    fn stroke_option(stroke: &Stroke) -> StrokeOptions {
+        let line_cap = match stroke.line_cap {
+            LineCap::Butt => NanovgLineCap::Butt,
+            LineCap::Round => NanovgLineCap::Round,
+            LineCap::Square => NanovgLineCap::Square,
+        };
+        let line_join = match stroke.line_join {
+            LineJoin::Miter => NanovgLineJoin::Miter,
+            LineJoin::Round => NanovgLineJoin::Round,
+            LineJoin::Bevel => NanovgLineJoin::Bevel,
+        };
+        StrokeOptions {
+            width: stroke.width,
+            line_cap,
+            line_join,
+            miter_limit: stroke.miter_limit,
+            ..Default::default()
+        }
+    }
+
+    fn text_color(text: &Text) -> NanovgColor {
+        ToNanovgPaint::to_nanovg_color(
+            text.fill.and_then(|fill| if let Paint::Color(color) = fill.paint {
+                Some(color)
+            } else {
+                None
+            }).unwrap_or_default()
+        )
+    }
+
+    fn text_options(text: &Text) -> TextOptions {
+        TextOptions {
+            color: Self::text_color(text),
+            size: text.font_size.val(),
+            align: Self::alignment(&text.align.0, &text.align.1),
+            transform: Self::to_nanovg_transform(text.transform.as_ref()),
+            ..Default::default()
+        }
+    }
+
+    fn alignment(align_hor: &AlignHor, align_ver: &AlignVer) -> Alignment {
+        let mut align = Alignment::new();
+        align = match *align_hor {
+            AlignHor::Left => align.left(),
+            AlignHor::Right => align.right(),
+            AlignHor::Center => align.center(),
+        };
+        match *align_ver {
+            AlignVer::Bottom => align.bottom(),
+            AlignVer::Middle => align.middle(),
+            AlignVer::Baseline => align.baseline(),
+            AlignVer::Top => align.top(),
+        }
+    }
+}
+
+struct Recalc<'a> {
+    measurer: &'a dyn TextMeasurer,
+    cache: Option<&'a mut RecalcCache>,
+    styles: &'a HashMap<NodePath, NodeStyle>,
+    fonts: &'a FontFamilies,
+    layouts: &'a mut Layouts,
+    profiler: Option<&'a mut dyn Profiler>,
+    font_names: Option<&'a mut HashSet<String>>,
+    stats: RenderStats,
+    path: NodePath,
+}
+
+impl<'a> Recalc<'a> {
+    fn new(measurer: &'a dyn TextMeasurer,
+           cache: Option<&'a mut RecalcCache>,
+           styles: &'a HashMap<NodePath, NodeStyle>,
+           fonts: &'a FontFamilies,
+           layouts: &'a mut Layouts) -> Self
+    {
+        Self {
+            measurer,
+            cache,
+            styles,
+            fonts,
+            layouts,
+            profiler: None,
+            font_names: None,
+            stats: RenderStats::default(),
+            path: Vec::new(),
+        }
+    }
+
+    fn with_profiler(mut self, profiler: Option<&'a mut dyn Profiler>) -> Self {
+        self.profiler = profiler;
+        self
+    }
+
+    /// Collects the font names of the texts laid out, the ones of cached texts were met
+    /// by an earlier pass.
+    fn with_font_names(mut self, font_names: Option<&'a mut HashSet<String>>) -> Self {
+        self.font_names = font_names;
+        self
+    }
+
+    fn run(mut self, draw: &mut dyn Drawable, bound: BoundingBox) -> RenderStats {
+        self.node(draw, bound, None);
+        self.stats
+    }
+
+    fn node(&mut self,
+            draw: &mut dyn Drawable,
+            parent_bound: BoundingBox,
+            text: Option<&Text>) -> BoundingBox
     {
-        let name = name.into();
-        let display_path = path.as_ref().display();
-        NanovgFont::from_file(
-            self.context.as_ref().ok_or(NanovgRendererError::ContextIsNotInit)?,
-            name.as_str(),
-            path.as_ref()
-        ).map_err(|e| NanovgRendererError::CreateFontError(e, format!("{}", display_path)))?;
-        Ok(())
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.begin(RenderPass::Recalc, &self.path);
+        }
+
+        let reused = self.cache.as_ref().and_then(|cache| cache.reuse(&self.path, parent_bound, text));
+        let bound = match reused {
+            Some(bound) => {
+                self.stats.cached_nodes += 1;
+                bound
+            },
+            None => {
+                self.stats.recalculated_nodes += 1;
+                let bound = self.recalc(draw, parent_bound, text);
+                if let Some(ref mut cache) = self.cache {
+                    cache.store(&self.path, parent_bound, text, bound);
+                }
+                bound
+            },
+        };
+
+        self.layouts.bounds.insert(self.path.clone(), bound);
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.end(RenderPass::Recalc, &self.path);
+        }
+        bound
     }
 
-    pub fn render_recalc(frame: &Frame,
-                         draw: &mut dyn Drawable,
-                         parent_bound: BoundingBox,
-                         text: Option<&Text>) -> BoundingBox
+    fn recalc(&mut self,
+              draw: &mut dyn Drawable,
+              parent_bound: BoundingBox,
+              text: Option<&Text>) -> BoundingBox
     {
         let mut bound = parent_bound;
+        let constraints = self.styles.get(&self.path).and_then(|style| style.constraints);
+        let anchor = self.styles.get(&self.path).and_then(|style| style.anchor);
+        let parent_size = (parent_bound.width(), parent_bound.height());
 
         if let Some(shape) = draw.shape_mut() {
             match shape {
@@ -193,8 +1174,22 @@ impl NanovgRenderer {
                     if r.y.set_by_pct(parent_bound.height()) {
                         r.y.0 += parent_bound.min_y;
                     }
-                    r.width.set_by_pct(parent_bound.width());
-                    r.height.set_by_pct(parent_bound.height());
+                    let width_pct = r.width.set_by_pct(parent_bound.width());
+                    let height_pct = r.height.set_by_pct(parent_bound.height());
+                    if let Some(constraints) = constraints.filter(|_| width_pct || height_pct) {
+                        let (width, height) = constraints.apply(
+                            (r.width.val(), r.height.val()),
+                            parent_size,
+                            (width_pct, height_pct),
+                        );
+                        r.width.0 = width;
+                        r.height.0 = height;
+                    }
+                    if let Some(anchor) = anchor {
+                        let (x, y) = anchor.position((r.width.val(), r.height.val()), &parent_bound);
+                        r.x.0 = x;
+                        r.y.0 = y;
+                    }
 
                     bound = BoundingBox {
                         min_x: r.x.val(),
@@ -210,7 +1205,17 @@ impl NanovgRenderer {
                     if c.cy.set_by_pct(parent_bound.height()) {
                         c.cy.0 += parent_bound.min_y;
                     }
-                    c.r.set_by_pct(parent_bound.width().min(parent_bound.height()));
+                    if c.r.set_by_pct(parent_bound.width().min(parent_bound.height())) {
+                        if let Some(constraints) = constraints {
+                            c.r.0 = NanovgRenderer::constrained_radius(&constraints, c.r.val(), parent_size);
+                        }
+                    }
+                    if let Some(anchor) = anchor {
+                        let r = c.r.val();
+                        let (x, y) = anchor.position((r * 2.0, r * 2.0), &parent_bound);
+                        c.cx.0 = x + r;
+                        c.cy.0 = y + r;
+                    }
 
                     let (cx, cy, r) = (c.cx.val(), c.cy.val(), c.r.val());
                     bound = BoundingBox {
@@ -221,65 +1226,120 @@ impl NanovgRenderer {
                     };
                 },
                 Shape::Text(ref mut t) => {
+                    if let Some(ref mut font_names) = self.font_names {
+                        if !font_names.contains(&t.font_name) {
+                            font_names.insert(t.font_name.clone());
+                        }
+                    }
                     if t.x.set_by_pct(parent_bound.width()) {
                         t.x.0 += parent_bound.min_x;
                     }
                     if t.y.set_by_pct(parent_bound.height()) {
                         t.y.0 += parent_bound.min_y;
                     }
-
-                    let text = t.clone();
-                    return Self::calc_inner_bound(frame, draw, bound, Some(&text));
+                    let mut text = t.clone();
+                    if let Some(anchor) = anchor {
+                        let (x, y) = anchor.position((0.0, 0.0), &parent_bound);
+                        text.x.0 = x;
+                        text.y.0 = y;
+                        let (align_hor, align_ver) = anchor.text_align();
+                        text.align.0 = align_hor;
+                        text.align.1 = align_ver;
+                    }
+                    if let Some(flow) = self.styles.get(&self.path).and_then(|style| style.flow) {
+                        return self.flow(draw, &text, &flow, parent_bound);
+                    }
+                    return self.inner_bound(draw, bound, Some(&text));
+                },
+                Shape::Path(ref p) => {
+                    let units = self.styles.get(&self.path)
+                        .and_then(|style| style.path_units)
+                        .map(|units| units.matrix(&parent_bound));
+                    match units {
+                        Some(units) => self.layouts.paths.insert(self.path.clone(), units),
+                        None => self.layouts.paths.remove(&self.path),
+                    };
+                    let units = units.unwrap_or(path::IDENTITY);
+                    if let Some(path_bound) = path::bound(&path::segments(&p.cmd), &units, None) {
+                        bound = path_bound;
+                    }
                 },
                 Shape::Word(ref w) => {
                     if let Some(text) = text {
-                        let nanovg_font = NanovgFont::find(frame.context(), text.font_name.as_str())
-                            .expect(&format!("Font '{}' not found", text.font_name));
-
-                        let text_options = if let AlignHor::Center = text.align.0 {
-                            // Fix nanovg text_bounds bug for centered text
-                            let mut text = text.clone();
-                            text.align.0 = AlignHor::Left;
-                            Self::text_options(&text)
+                        let measurer = self.measurer;
+                        let shaped = shaping::shape(w.as_ref());
+                        let word: &str = &shaped;
+                        let spans = style::word_spans(self.styles, &self.path);
+                        let text_style = self.path.split_last()
+                            .and_then(|(_, text_path)| self.styles.get(text_path));
+                        let overflow = text_style
+                            .and_then(|style| style.overflow)
+                            .map(|overflow| (overflow, parent_bound));
+                        // The draw pass reads the position of an anchored text from its runs,
+                        // the `Text` itself keeps the position it was given
+                        let anchored = text_style.map_or(false, |style| style.anchor.is_some());
+                        if spans.is_empty() && overflow.is_none() && !anchored
+                            && !self.fonts.contains(&text.font_name)
+                        {
+                            bound = match self.cache {
+                                Some(ref mut cache) => cache.word_bound(
+                                    text,
+                                    word,
+                                    || Measure::word_bounds(measurer, text, word),
+                                ),
+                                None => Measure::word_bounds(measurer, text, word),
+                            };
+                            self.layouts.text.remove(&self.path);
                         } else {
-                            Self::text_options(text)
-                        };
-
-                        let mut text_bounds = frame.text_box_bounds(
-                            nanovg_font,
-                            (text.x.val(), text.y.val()),
-                            w,
-                            text_options,
-                        );
-
-                        // Fix nanovg text_bounds bug for centered text
-                        if let AlignHor::Center = text.align.0 {
-                            let half_width = (text_bounds.max_x - text_bounds.min_x) / 2.0;
-                            text_bounds.min_x -= half_width;
-                            text_bounds.max_x -= half_width;
+                            let settings = SpanSettings::new(self.fonts, text, &spans);
+                            let content = match overflow {
+                                Some((overflow, parent_bound)) => {
+                                    let measure = settings.measure(measurer);
+                                    let max_width = text::available_width(text, parent_bound);
+                                    Cow::Owned(text::fit_word(&measure, w.as_ref(), max_width, overflow))
+                                },
+                                None => Cow::Borrowed(word),
+                            };
+                            let run = text::word_run(measurer, text, &settings, &content);
+                            bound = match text.transform {
+                                Some(ref transform) => run.bound.transform(&transform.matrix),
+                                None => run.bound,
+                            };
+                            self.layouts.text.insert(self.path.clone(), vec![run]);
                         }
-
-                        bound = BoundingBox {
-                            min_x: text_bounds.min_x,
-                            min_y: text_bounds.min_y,
-                            max_x: text_bounds.max_x,
-                            max_y: text_bounds.max_y,
-                        };
                     }
                 },
                 _ => (),
             }
         }
 
-        let inner_bound = Self::calc_inner_bound(frame, draw, bound, text);
+        let has_childs = draw.childs().map_or(false, |childs| childs.into_iter().next().is_some());
+        let inner_bound = self.inner_bound(draw, bound, text);
+        let inner_bound = Self::local_bound(draw.shape(), inner_bound);
 
+        let mut shift = (0.0, 0.0);
         if let Some(shape) = draw.shape_mut() {
             match shape {
                 Shape::Rect(ref mut r) => {
                     r.x.set_by_auto(inner_bound.min_x);
                     r.y.set_by_auto(inner_bound.min_y);
-                    r.width.set_by_auto(inner_bound.width());
-                    r.height.set_by_auto(inner_bound.height());
+                    let width_auto = r.width.set_by_auto(inner_bound.width());
+                    let height_auto = r.height.set_by_auto(inner_bound.height());
+                    if let Some(constraints) = constraints.filter(|_| width_auto || height_auto) {
+                        let (width, height) = constraints.apply(
+                            (r.width.val(), r.height.val()),
+                            parent_size,
+                            (width_auto, height_auto),
+                        );
+                        r.width.0 = width;
+                        r.height.0 = height;
+                    }
+                    if let Some(anchor) = anchor {
+                        let (x, y) = anchor.position((r.width.val(), r.height.val()), &parent_bound);
+                        shift = (x - r.x.val(), y - r.y.val());
+                        r.x.0 = x;
+                        r.y.0 = y;
+                    }
 
                     bound = BoundingBox {
                         min_x: r.x.val(),
@@ -291,7 +1351,18 @@ impl NanovgRenderer {
                 Shape::Circle(ref mut c) => {
                     c.cx.set_by_auto(inner_bound.min_x + inner_bound.width() / 2.0);
                     c.cy.set_by_auto(inner_bound.min_y + inner_bound.height() / 2.0);
-                    c.r.set_by_auto(inner_bound.width().max(inner_bound.height()) / 2.0);
+                    if c.r.set_by_auto(inner_bound.width().max(inner_bound.height()) / 2.0) {
+                        if let Some(constraints) = constraints {
+                            c.r.0 = NanovgRenderer::constrained_radius(&constraints, c.r.val(), parent_size);
+                        }
+                    }
+                    if let Some(anchor) = anchor {
+                        let r = c.r.val();
+                        let (x, y) = anchor.position((r * 2.0, r * 2.0), &parent_bound);
+                        shift = (x + r - c.cx.val(), y + r - c.cy.val());
+                        c.cx.0 = x + r;
+                        c.cy.0 = y + r;
+                    }
 
                     let (cx, cy, r) = (c.cx.val(), c.cy.val(), c.r.val());
                     bound = BoundingBox {
@@ -304,20 +1375,135 @@ impl NanovgRenderer {
                 _ => (),
             }
         }
-        bound
+
+        let inner_bound = if has_childs && shift != (0.0, 0.0) {
+            self.layouts.shifts.insert(self.path.clone(), shift);
+            inner_bound.translate(shift)
+        } else {
+            self.layouts.shifts.remove(&self.path);
+            inner_bound
+        };
+
+        let scroll = self.styles.get(&self.path).and_then(|style| style.scroll);
+        if let (Some(Shape::Rect(_)), Some(_)) = (draw.shape(), scroll) {
+            let content = if has_childs { bound.union(&inner_bound) } else { bound };
+            self.layouts.scrolls.insert(self.path.clone(), ScrollExtent { viewport: bound, content });
+        } else {
+            self.layouts.scrolls.remove(&self.path);
+        }
+        self.drawn_bound(draw.shape(), bound)
+    }
+
+    /// Children bounds are in root coordinates, an auto sized shape with a transform fits
+    /// their bound mapped back to its own coordinates so the transformed shape encloses them.
+    fn local_bound(shape: Option<&Shape>, inner_bound: BoundingBox) -> BoundingBox {
+        let transform = match shape {
+            Some(Shape::Rect(r)) => r.transform.as_ref(),
+            Some(Shape::Circle(c)) => c.transform.as_ref(),
+            _ => None,
+        };
+        transform
+            .and_then(|transform| path::invert(&transform.matrix))
+            .map_or(inner_bound, |inverse| inner_bound.transform(&inverse))
+    }
+
+    /// Bound of what a shape draws: `bound` is its geometry, which its children are laid out in,
+    /// while the parent gets it with the stroke outline and mapped through the shape transform.
+    /// Shapes are drawn with their own transforms only, so relative and absolute transforms
+    /// both map to root coordinates.
+    fn drawn_bound(&self, shape: Option<&Shape>, bound: BoundingBox) -> BoundingBox {
+        let matrix = |transform: Option<&Transform>| transform.map_or(path::IDENTITY, |transform| transform.matrix);
+        let drawn = match shape {
+            Some(Shape::Rect(r)) => {
+                path::bound(&path::rect_segments(&bound), &matrix(r.transform.as_ref()), r.stroke.as_ref())
+            },
+            Some(Shape::Circle(c)) => {
+                let segments = path::circle_segments((c.cx.val(), c.cy.val()), c.r.val());
+                path::bound(&segments, &matrix(c.transform.as_ref()), c.stroke.as_ref())
+            },
+            Some(Shape::Path(p)) => {
+                let segments = path::segments(&p.cmd);
+                let segments = match self.layouts.paths.get(&self.path) {
+                    Some(units) => path::map_segments(&segments, units),
+                    None => segments,
+                };
+                path::bound(&segments, &matrix(p.transform.as_ref()), p.stroke.as_ref())
+            },
+            _ => None,
+        };
+        drawn.unwrap_or(bound)
+    }
+
+    fn flow(&mut self,
+            draw: &mut dyn Drawable,
+            text: &Text,
+            flow: &TextFlow,
+            parent_bound: BoundingBox) -> BoundingBox
+    {
+        let (styles, fonts) = (self.styles, self.fonts);
+        let mut word_paths = Vec::new();
+        let mut contents = Vec::new();
+        if let Some(childs) = draw.childs() {
+            for (idx, child) in childs.into_iter().enumerate() {
+                if let Some(Shape::Word(w)) = child.shape() {
+                    let mut path = self.path.clone();
+                    path.push(idx);
+                    if style::visibility(styles, &path) == Visibility::Collapsed {
+                        continue;
+                    }
+                    contents.push(shaping::shape(w.as_ref()).into_owned());
+                    word_paths.push(path);
+                }
+            }
+        }
+        let words: Vec<_> = word_paths.iter().zip(contents.iter())
+            .map(|(path, content)| FlowWord {
+                content,
+                settings: SpanSettings::new(fonts, text, &style::word_spans(styles, path)),
+            })
+            .collect();
+
+        let text_spans: Vec<_> = styles.get(&self.path).and_then(|style| style.span.as_ref()).into_iter().collect();
+        let base = SpanSettings::new(fonts, text, &text_spans);
+        let max_width = flow.max_width.unwrap_or_else(|| parent_bound.width());
+        let (runs, bound) = text::flow(self.measurer, text, &base, &words, max_width);
+        for (path, word_runs) in word_paths.into_iter().zip(runs) {
+            let word_bound = word_runs.iter()
+                .map(|run| match text.transform {
+                    Some(ref transform) => run.bound.transform(&transform.matrix),
+                    None => run.bound,
+                })
+                .fold(None, |union: Option<BoundingBox>, bound| {
+                    Some(union.map_or(bound, |union| union.union(&bound)))
+                });
+            match word_bound {
+                Some(word_bound) => self.layouts.bounds.insert(path.clone(), word_bound),
+                None => self.layouts.bounds.remove(&path),
+            };
+            self.layouts.text.insert(path, word_runs);
+        }
+
+        match text.transform {
+            Some(ref transform) => bound.transform(&transform.matrix),
+            None => bound,
+        }
     }
 
-    fn calc_inner_bound(frame: &Frame,
-                        draw: &mut dyn Drawable,
-                        bound: BoundingBox,
-                        text: Option<&Text>) -> BoundingBox
+    fn inner_bound(&mut self,
+                   draw: &mut dyn Drawable,
+                   bound: BoundingBox,
+                   text: Option<&Text>) -> BoundingBox
     {
         let mut child_bounds = Vec::new();
         if let Some(childs) = draw.childs_mut() {
-            for child in childs {
-                child_bounds.push(
-                    Self::render_recalc(frame, child, bound, text)
-                );
+            for (idx, child) in childs.into_iter().enumerate() {
+                self.path.push(idx);
+                if style::visibility(self.styles, &self.path) != Visibility::Collapsed {
+                    child_bounds.push(
+                        self.node(child, bound, text)
+                    );
+                }
+                self.path.pop();
             }
         }
 
@@ -342,110 +1528,303 @@ impl NanovgRenderer {
             inner_bound
         }
     }
+}
+
+struct Draw<'a, 'f> {
+    frame: &'a Frame<'f>,
+    styles: &'a HashMap<NodePath, NodeStyle>,
+    fonts: &'a FontFamilies,
+    layouts: &'a Layouts,
+    profiler: Option<&'a mut dyn Profiler>,
+    stats: Cell<RenderStats>,
+    path: NodePath,
+    offset: (Real, Real),
+    clip: Option<BoundingBox>,
+    alpha: Real,
+    snap: Option<PixelSnap>,
+    overlays: Vec<Overlay<'a>>,
+    in_overlay: bool,
+    painted: Option<&'a mut Vec<PaintedNode>>,
+}
+
+impl<'a, 'f> Draw<'a, 'f> {
+    fn new(frame: &'a Frame<'f>,
+           styles: &'a HashMap<NodePath, NodeStyle>,
+           fonts: &'a FontFamilies,
+           layouts: &'a Layouts) -> Self
+    {
+        Self {
+            frame,
+            styles,
+            fonts,
+            layouts,
+            profiler: None,
+            stats: Cell::new(RenderStats::default()),
+            path: Vec::new(),
+            offset: (0.0, 0.0),
+            clip: None,
+            alpha: 1.0,
+            snap: None,
+            overlays: Vec::new(),
+            in_overlay: false,
+            painted: None,
+        }
+    }
+
+    fn with_profiler(mut self, profiler: Option<&'a mut dyn Profiler>) -> Self {
+        self.profiler = profiler;
+        self
+    }
+
+    fn with_painted(mut self, painted: Option<&'a mut Vec<PaintedNode>>) -> Self {
+        self.painted = painted;
+        self
+    }
+
+    fn with_pixel_snap(mut self, snap: Option<PixelSnap>) -> Self {
+        self.snap = snap;
+        self
+    }
+
+    /// Draws `draw` and then its overlay layer, adding the counters to `stats`.
+    fn run(mut self, draw: &'a dyn Drawable, stats: RenderStats) -> RenderStats {
+        self.stats.set(stats);
+        self.node(draw, None);
+
+        let mut overlays = mem::take(&mut self.overlays);
+        let styles = self.styles;
+        overlays.sort_by_key(|overlay| layer::z_index(styles, &overlay.path));
+        self.in_overlay = true;
+        for overlay in overlays {
+            self.path = overlay.path;
+            self.offset = overlay.offset;
+            self.clip = None;
+            self.alpha = overlay.alpha;
+            self.node(overlay.draw, overlay.text);
+        }
+        self.stats.get()
+    }
+
+    fn count<F: FnOnce(&mut RenderStats)>(&self, update: F) {
+        let mut stats = self.stats.get();
+        update(&mut stats);
+        self.stats.set(stats);
+    }
+
+    fn count_path(&self, fill: bool, stroke: bool) {
+        self.count(|stats| {
+            stats.paths += 1;
+            stats.fills += fill as usize;
+            stats.strokes += stroke as usize;
+        });
+    }
+
+    /// Path options of a shape moved by the scroll offsets and clipped to the viewports
+    /// of the scrolled nodes it is in, with the opacity of its ancestors as global alpha.
+    fn path_options(&self, transform: Option<&Transform>) -> PathOptions {
+        let options = NanovgRenderer::path_options(transform);
+        PathOptions {
+            clip: self.clip(),
+            alpha: options.alpha * self.alpha,
+            transform: self.scrolled(options.transform),
+            ..options
+        }
+    }
+
+    /// Text has no global alpha option, the opacity goes into the color.
+    fn text_options(&self, options: TextOptions) -> TextOptions {
+        let color = options.color;
+        TextOptions {
+            clip: self.clip(),
+            color: NanovgColor::new(color.red(), color.green(), color.blue(), color.alpha() * self.alpha),
+            transform: self.scrolled(options.transform),
+            ..options
+        }
+    }
+
+    fn scrolled(&self, transform: Option<NanovgTransform>) -> Option<NanovgTransform> {
+        if self.offset == (0.0, 0.0) {
+            return transform;
+        }
+        let translation = [1.0, 0.0, 0.0, 1.0, self.offset.0, self.offset.1];
+        let mut transform = transform.unwrap_or_else(|| {
+            let mut nanovg_transform = NanovgTransform::new();
+            nanovg_transform.matrix = path::IDENTITY;
+            nanovg_transform
+        });
+        transform.matrix = path::multiply(&translation, &transform.matrix);
+        Some(transform)
+    }
+
+    fn clip(&self) -> Clip {
+        match self.clip {
+            Some(clip) => Clip::Scissor(Scissor {
+                x: clip.min_x,
+                y: clip.min_y,
+                width: clip.width(),
+                height: clip.height(),
+                transform: None,
+            }),
+            None => Clip::None,
+        }
+    }
+
+    /// Pixel snapping of an untransformed shape with the snapped width of its stroke.
+    fn pixel_snap(&self, transform: Option<&Transform>, stroke: Option<&Stroke>) -> Option<(PixelSnap, Option<Real>)> {
+        let snap = self.snap.filter(|_| transform.is_none())?;
+        Some((snap, stroke.map(|stroke| snap.stroke_width(stroke.width))))
+    }
+
+    fn stroke_options(stroke: &Stroke, snapped_width: Option<Real>) -> StrokeOptions {
+        StrokeOptions {
+            width: snapped_width.unwrap_or(stroke.width),
+            ..NanovgRenderer::stroke_option(stroke)
+        }
+    }
+
+    /// Viewport of a scrolled node in frame coordinates and its clamped offset.
+    fn scroll(&self, shape: Option<&Shape>) -> Option<(Scroll, ScrollExtent)> {
+        match shape {
+            Some(Shape::Rect(_)) => self.styles.get(&self.path)
+                .and_then(|style| style.scroll)
+                .and_then(|scroll| self.layouts.scrolls.get(&self.path).map(|extent| (scroll, *extent))),
+            _ => None,
+        }
+    }
+
+    fn scrollbars(&self, scroll: &Scroll, extent: &ScrollExtent) {
+        let scrollbars = match scroll.scrollbars {
+            Some(ref scrollbars) => scrollbars,
+            None => return,
+        };
+        let thumbs = extent.thumbs(scroll.offset, scrollbars);
+        if thumbs.is_empty() {
+            return;
+        }
+
+        let color = scrollbars.color
+            .map(ToNanovgPaint::to_nanovg_color)
+            .unwrap_or_else(|| NanovgColor::new(0.5, 0.5, 0.5, 0.6));
+        self.count_path(true, false);
+        self.frame.path(
+            |path| {
+                for thumb in thumbs.iter() {
+                    path.rect((thumb.min_x, thumb.min_y), (thumb.width(), thumb.height()));
+                }
+                path.fill(color, Default::default());
+            },
+            self.path_options(None),
+        );
+    }
+
+    fn node(&mut self, draw: &'a dyn Drawable, mut text: Option<&'a Text>) {
+        if style::visibility(self.styles, &self.path) != Visibility::Visible {
+            return;
+        }
+        if !self.in_overlay && layer::is_overlay(self.styles, &self.path) {
+            self.overlays.push(Overlay {
+                path: self.path.clone(),
+                draw,
+                text,
+                offset: self.offset,
+                alpha: self.alpha,
+            });
+            return;
+        }
+
+        let outer_alpha = self.alpha;
+        if let Some(opacity) = self.styles.get(&self.path).and_then(|style| style.opacity) {
+            self.alpha *= opacity.max(0.0).min(1.0);
+        }
+
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.begin(RenderPass::Draw, &self.path);
+        }
+        self.count(|stats| stats.nodes += 1);
+        self.paint(draw.shape(), text);
 
-    fn render_draw<'a>(frame: &Frame, draw: &'a dyn Drawable, mut text: Option<&'a Text>) {
+        let frame = self.frame;
         if let Some(shape) = draw.shape() {
             match shape {
                 Shape::Rect(ref r) => {
+                    self.count_path(r.fill.is_some(), r.stroke.is_some());
+                    let snap = self.pixel_snap(r.transform.as_ref(), r.stroke.as_ref());
+                    let rect = BoundingBox {
+                        min_x: r.x.val(),
+                        min_y: r.y.val(),
+                        max_x: r.x.val() + r.width.val(),
+                        max_y: r.y.val() + r.height.val(),
+                    };
+                    let (rect, stroke_width) = match snap {
+                        Some((snap, stroke_width)) => (snap.rect(&rect, stroke_width), stroke_width),
+                        None => (rect, None),
+                    };
                     frame.path(
                         |path| {
-                            path.rect((r.x.val(), r.y.val()), (r.width.val(), r.height.val()));
+                            path.rect((rect.min_x, rect.min_y), (rect.width(), rect.height()));
                             if let Some(fill) = r.fill {
                                 path.fill(ToNanovgPaint(fill.paint), Default::default());
                             };
                             if let Some(stroke) = r.stroke {
                                 path.stroke(
                                     ToNanovgPaint(stroke.paint),
-                                    Self::stroke_option(&stroke)
+                                    Self::stroke_options(&stroke, stroke_width)
                                 );
                             }
                         },
-                        Self::path_options(r.transform.as_ref()),
+                        self.path_options(r.transform.as_ref()),
                     );
                 },
                 Shape::Circle(ref c) => {
+                    self.count_path(c.fill.is_some(), c.stroke.is_some());
+                    let center = (c.cx.val(), c.cy.val());
+                    let (center, r, stroke_width) = match self.pixel_snap(c.transform.as_ref(), c.stroke.as_ref()) {
+                        Some((snap, stroke_width)) => {
+                            (snap.point(center, stroke_width), snap.radius(c.r.val()), stroke_width)
+                        },
+                        None => (center, c.r.val(), None),
+                    };
                     frame.path(
                         |path| {
-                            path.circle((c.cx.val(), c.cy.val()), c.r.val());
+                            path.circle(center, r);
                             if let Some(fill) = c.fill {
                                 path.fill(ToNanovgPaint(fill.paint), Default::default());
                             };
                             if let Some(stroke) = c.stroke {
                                 path.stroke(
                                     ToNanovgPaint(stroke.paint),
-                                    Self::stroke_option(&stroke)
+                                    Self::stroke_options(&stroke, stroke_width)
                                 );
                             }
                         },
-                        Self::path_options(c.transform.as_ref()),
+                        self.path_options(c.transform.as_ref()),
                     );
                 },
                 Shape::Path(ref p) => {
+                    self.count_path(p.fill.is_some(), p.stroke.is_some());
+                    let segments = path::segments(&p.cmd);
+                    let segments = match self.layouts.paths.get(&self.path) {
+                        Some(units) => path::map_segments(&segments, units),
+                        None => segments,
+                    };
+                    let (segments, stroke_width) = match self.pixel_snap(p.transform.as_ref(), p.stroke.as_ref()) {
+                        Some((snap, stroke_width)) => {
+                            (snap.segments(&segments, stroke_width), stroke_width)
+                        },
+                        _ => (segments, None),
+                    };
                     frame.path(
                         |path| {
-                            use exgui::PathCommand::*;
-
-                            let mut last_xy = [0.0_f32, 0.0];
-                            let mut bez_ctrls = [(0.0_f32, 0.0), (0.0_f32, 0.0)];
-
-                            for cmd in p.cmd.iter() {
-                                match cmd {
-                                    Move(ref xy) => {
-                                        last_xy = *xy;
-                                        path.move_to((last_xy[0], last_xy[1]));
-                                    },
-                                    MoveRel(ref xy) => {
-                                        last_xy = [last_xy[0] + xy[0], last_xy[1] + xy[1]];
-                                        path.move_to((last_xy[0], last_xy[1]));
-                                    },
-                                    Line(ref xy) => {
-                                        last_xy = *xy;
-                                        path.line_to((last_xy[0], last_xy[1]));
-                                    },
-                                    LineRel(ref xy) => {
-                                        last_xy = [last_xy[0] + xy[0], last_xy[1] + xy[1]];
-                                        path.line_to((last_xy[0], last_xy[1]));
-                                    },
-                                    LineAlonX(ref x) => {
-                                        last_xy[0] = *x;
-                                        path.line_to((last_xy[0], last_xy[1]));
-                                    },
-                                    LineAlonXRel(ref x) => {
-                                        last_xy[0] += *x;
-                                        path.line_to((last_xy[0], last_xy[1]));
-                                    },
-                                    LineAlonY(ref y) => {
-                                        last_xy[1] = *y;
-                                        path.line_to((last_xy[0], last_xy[1]));
+                            for segment in segments {
+                                match segment {
+                                    Segment::Move(to) => path.move_to(to),
+                                    Segment::Line(to) => path.line_to(to),
+                                    Segment::Quad(ctrl, to) => path.quad_bezier_to(to, ctrl),
+                                    Segment::Cubic(ctrl1, ctrl2, to) => path.cubic_bezier_to(to, ctrl1, ctrl2),
+                                    Segment::Close => path.close(),
+                                    Segment::Unsupported(idx) => {
+                                        panic!("Not impl rendering cmd {:?}", p.cmd[idx]) // TODO: need refl impl
                                     },
-                                    LineAlonYRel(ref y) => {
-                                        last_xy[1] += *y;
-                                        path.line_to((last_xy[0], last_xy[1]));
-                                    },
-                                    Close => path.close(),
-                                    BezCtrl(ref xy) => {
-                                        bez_ctrls = [bez_ctrls[1], (xy[0], xy[1])];
-                                    },
-                                    BezCtrlRel(ref xy) => {
-                                        bez_ctrls = [bez_ctrls[1], (last_xy[0] + xy[0], last_xy[1] + xy[1])];
-                                    },
-                                    QuadBezTo(ref xy) => {
-                                        last_xy = *xy;
-                                        path.quad_bezier_to((last_xy[0], last_xy[1]), bez_ctrls[1]);
-                                    },
-                                    QuadBezToRel(ref xy) => {
-                                        last_xy = [last_xy[0] + xy[0], last_xy[1] + xy[1]];
-                                        path.quad_bezier_to((last_xy[0], last_xy[1]), bez_ctrls[1]);
-                                    },
-                                    CubBezTo(ref xy) => {
-                                        last_xy = *xy;
-                                        path.cubic_bezier_to((last_xy[0], last_xy[1]), bez_ctrls[0], bez_ctrls[1]);
-                                    },
-                                    CubBezToRel(ref xy) => {
-                                        last_xy = [last_xy[0] + xy[0], last_xy[1] + xy[1]];
-                                        path.cubic_bezier_to((last_xy[0], last_xy[1]), bez_ctrls[0], bez_ctrls[1]);
-                                    },
-                                    _ => panic!("Not impl rendering cmd {:?}", cmd), // TODO: need refl impl
                                 }
                             }
                             if let Some(fill) = p.fill {
@@ -454,11 +1833,11 @@ impl NanovgRenderer {
                             if let Some(stroke) = p.stroke {
                                 path.stroke(
                                     ToNanovgPaint(stroke.paint),
-                                    Self::stroke_option(&stroke)
+                                    Self::stroke_options(&stroke, stroke_width)
                                 );
                             }
                         },
-                        Self::path_options(p.transform.as_ref()),
+                        self.path_options(p.transform.as_ref()),
                     );
                 },
                 Shape::Text(ref t) => {
@@ -466,102 +1845,189 @@ impl NanovgRenderer {
                 },
                 Shape::Word(ref w) => {
                     if let Some(text) = text {
-                        let nanovg_font = NanovgFont::find(frame.context(), text.font_name.as_str())
-                            .expect(&format!("Font '{}' not found", text.font_name));
-                        let text_options = Self::text_options(text);
-
-                        frame.text(
-                            nanovg_font,
-                            (text.x.val(), text.y.val()),
-                            w,
-                            text_options,
-                        );
+                        let selection = self.styles.get(&self.path)
+                            .and_then(|style| style.selection)
+                            .map(|selection| (selection, self.word_geometry(text, w.as_ref())));
+                        if let Some((ref selection, ref geometry)) = selection {
+                            self.selection(text, selection, geometry);
+                        }
+
+                        match self.layouts.text.get(&self.path) {
+                            Some(runs) => self.runs(text, runs),
+                            None => {
+                                // A word in a missing font was laid out as empty text
+                                if let Ok(nanovg_font) = NanovgFont::find(frame.context(), text.font_name.as_str()) {
+                                    let text_options = self.text_options(NanovgRenderer::text_options(text));
+
+                                    self.count(|stats| stats.text_runs += 1);
+                                    frame.text(
+                                        nanovg_font,
+                                        (text.x.val(), text.y.val()),
+                                        &*shaping::shape(w.as_ref()),
+                                        text_options,
+                                    );
+                                }
+                            },
+                        }
+
+                        if let Some((ref selection, ref geometry)) = selection {
+                            self.caret(text, selection, geometry);
+                        }
                     }
                 },
                 Shape::Group(ref _g) => {},
             }
         }
+
+        let scroll = self.scroll(draw.shape());
+        let (outer_offset, outer_clip) = (self.offset, self.clip);
+        let scroll_offset = scroll.as_ref().map(|(scroll, extent)| extent.clamp(scroll.offset));
+        if let Some((_, ref extent)) = scroll {
+            let viewport = extent.viewport.translate(self.offset);
+            self.clip = Some(self.clip.map_or(viewport, |clip| clip.intersection(&viewport)));
+        }
+        let shift = self.layouts.shifts.get(&self.path).cloned();
+        if scroll_offset.is_some() || shift.is_some() {
+            self.offset = snap::child_offset(
+                self.snap,
+                self.offset,
+                scroll_offset.unwrap_or_default(),
+                shift.unwrap_or_default(),
+            );
+        }
         if let Some(childs) = draw.childs() {
-            for child in childs {
-                Self::render_draw(frame, child, text);
+            let childs: Vec<_> = childs.into_iter().collect();
+            for idx in layer::paint_order(self.styles, &mut self.path, childs.len()) {
+                self.path.push(idx);
+                self.node(childs[idx], text);
+                self.path.pop();
             }
         }
+        self.offset = outer_offset;
+        self.clip = outer_clip;
+        if let Some((ref scroll, ref extent)) = scroll {
+            self.scrollbars(scroll, extent);
+        }
+        self.alpha = outer_alpha;
+
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.end(RenderPass::Draw, &self.path);
+        }
     }
 
-    fn to_nanovg_transform(transform: Option<&Transform>) -> Option<NanovgTransform> {
-        transform.map(|transform| {
-            let mut nanovg_transform = NanovgTransform::new();
-            if transform.absolute {
-                nanovg_transform.absolute();
-            }
-            nanovg_transform.matrix = transform.matrix;
-            nanovg_transform
-        })
+    /// Records where the node is painted for hit testing and the debug overlay. A word is
+    /// painted once per run, so the lines of a flowed word don't catch the space between them.
+    fn paint(&mut self, shape: Option<&Shape>, text: Option<&Text>) {
+        let painted = match self.painted {
+            Some(ref mut painted) => painted,
+            None => return,
+        };
+        let bounds: Vec<_> = match (shape, self.layouts.text.get(&self.path)) {
+            (Some(Shape::Word(_)), Some(runs)) => {
+                let transform = text.and_then(|text| text.transform.as_ref());
+                runs.iter()
+                    .map(|run| transform.map_or(run.bound, |transform| run.bound.transform(&transform.matrix)))
+                    .collect()
+            },
+            _ => self.layouts.bounds.get(&self.path).cloned().into_iter().collect(),
+        };
+        for bound in bounds {
+            painted.push(PaintedNode {
+                path: self.path.clone(),
+                kind: ShapeKind::of(shape),
+                bound: bound.translate(self.offset),
+                clip: self.clip,
+            });
+        }
     }
 
-    fn path_options(transform: Option<&Transform>) -> PathOptions {
-        if let Some(transform) = transform {
-            let mut nanovg_transform = NanovgTransform::new();
-            if transform.absolute {
-                nanovg_transform.absolute();
-            }
-            nanovg_transform.matrix = transform.matrix;
-            PathOptions {
-                transform: Some(nanovg_transform),
-                ..Default::default()
-            }
-        } else {
-            PathOptions::default()
+    fn word_geometry(&self, text: &Text, content: &str) -> WordGeometry {
+        let spans = style::word_spans(self.styles, &self.path);
+        let settings = SpanSettings::new(self.fonts, text, &spans);
+        let runs = self.layouts.text.get(&self.path).map(Vec::as_slice);
+        WordGeometry::of_word(self.frame, text, &settings, &shaping::shaped(content), runs)
+    }
+
+    fn selection(&self, text: &Text, selection: &TextSelection, geometry: &WordGeometry) {
+        let rects = selection.range
+            .map(|(start, end)| geometry.selection(start, end))
+            .unwrap_or_default();
+        if rects.is_empty() {
+            return;
         }
+
+        let color = selection.selection_color
+            .map(ToNanovgPaint::to_nanovg_color)
+            .unwrap_or_else(|| NanovgColor::new(0.2, 0.4, 1.0, 0.35));
+        self.fill_rects(text, &rects, color);
     }
 
-    fn stroke_option(stroke: &Stroke) -> StrokeOptions {
-        let line_cap = match stroke.line_cap {
-            LineCap::Butt => NanovgLineCap::Butt,
-            LineCap::Round => NanovgLineCap::Round,
-            LineCap::Square => NanovgLineCap::Square,
-        };
-        let line_join = match stroke.line_join {
-            LineJoin::Miter => NanovgLineJoin::Miter,
-            LineJoin::Round => NanovgLineJoin::Round,
-            LineJoin::Bevel => NanovgLineJoin::Bevel,
-        };
-        StrokeOptions {
-            width: stroke.width,
-            line_cap,
-            line_join,
-            miter_limit: stroke.miter_limit,
-            ..Default::default()
+    fn caret(&self, text: &Text, selection: &TextSelection, geometry: &WordGeometry) {
+        if let Some(index) = selection.caret {
+            let color = selection.caret_color
+                .map(ToNanovgPaint::to_nanovg_color)
+                .unwrap_or_else(|| NanovgRenderer::text_color(text));
+            if let Some(rect) = geometry.caret(index, selection.caret_width) {
+                self.fill_rects(text, &[rect], color);
+            }
         }
     }
 
-    fn text_options(text: &Text) -> TextOptions {
-        let color = ToNanovgPaint::to_nanovg_color(
-            text.fill.and_then(|fill| if let Paint::Color(color) = fill.paint {
-                Some(color)
-            } else {
-                None
-            }).unwrap_or_default()
+    fn fill_rects(&self, text: &Text, rects: &[BoundingBox], color: NanovgColor) {
+        self.count_path(true, false);
+        self.frame.path(
+            |path| {
+                for rect in rects {
+                    path.rect((rect.min_x, rect.min_y), (rect.width(), rect.height()));
+                }
+                path.fill(color, Default::default());
+            },
+            self.path_options(text.transform.as_ref()),
         );
-        let mut align = Alignment::new();
-        align = match text.align.0 {
-            AlignHor::Left => align.left(),
-            AlignHor::Right => align.right(),
-            AlignHor::Center => align.center(),
-        };
-        align = match text.align.1 {
-            AlignVer::Bottom => align.bottom(),
-            AlignVer::Middle => align.middle(),
-            AlignVer::Baseline => align.baseline(),
-            AlignVer::Top => align.top(),
-        };
+    }
 
-        TextOptions {
-            color,
-            size: text.font_size.val(),
-            align,
-            transform: Self::to_nanovg_transform(text.transform.as_ref()),
-            ..Default::default()
+    fn runs(&self, text: &Text, runs: &[TextRun]) {
+        let spans = style::word_spans(self.styles, &self.path);
+        let settings = SpanSettings::new(self.fonts, text, &spans);
+        let color = settings.color
+            .map(ToNanovgPaint::to_nanovg_color)
+            .unwrap_or_else(|| NanovgRenderer::text_color(text));
+        let embolden = settings.embolden_offset();
+
+        for run in runs {
+            let mut offsets = vec![0.0];
+            if embolden > 0.0 {
+                offsets.push(embolden);
+            }
+            for offset in offsets {
+                let nanovg_font = match NanovgFont::find(self.frame.context(), settings.font_name) {
+                    Ok(nanovg_font) => nanovg_font,
+                    // The runs of a missing font were laid out as empty text
+                    Err(_) => return,
+                };
+                self.count(|stats| stats.text_runs += 1);
+                self.frame.text(
+                    nanovg_font,
+                    (run.x + offset, run.y),
+                    &run.content,
+                    self.text_options(TextOptions {
+                        color,
+                        size: settings.font_size,
+                        letter_spacing: settings.letter_spacing,
+                        line_height: settings.line_height,
+                        align: Alignment::new().left().baseline(),
+                        transform: NanovgRenderer::run_transform(
+                            text.transform.as_ref(),
+                            if settings.synthetic_oblique { Some(run.y) } else { None },
+                        ),
+                        ..Default::default()
+                    }),
+                );
+            }
+
+            if !run.decorations.is_empty() {
+                self.fill_rects(text, &run.decorations, color);
+            }
         }
     }
 }
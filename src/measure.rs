@@ -0,0 +1,336 @@
+use std::cell::Cell;
+use nanovg::{Frame, Font as NanovgFont, TextOptions, Alignment};
+use exgui::{Real, Text, AlignHor, AlignVer};
+use crate::BoundingBox;
+
+thread_local! {
+    static MEASUREMENTS: Cell<usize> = Cell::new(0);
+}
+
+/// Number of text measurements made on this thread.
+pub(crate) fn measurement_count() -> usize {
+    MEASUREMENTS.with(|count| count.get())
+}
+
+fn count_measurement() {
+    MEASUREMENTS.with(|count| count.set(count.get() + 1));
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    pub ascender: Real,
+    pub descender: Real,
+    pub line_height: Real,
+}
+
+/// The glyph of the char at `byte_index` spans `min_x..max_x`, where `min_x` is the pen
+/// position it is drawn at.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct GlyphPosition {
+    pub byte_index: usize,
+    pub min_x: Real,
+    pub max_x: Real,
+}
+
+pub(crate) struct AlignedText {
+    pub origin: (Real, Real),
+    pub advance: Real,
+    pub bound: BoundingBox,
+}
+
+/// Source of text measurements for the layout pass, implemented by the nanovg `Frame`
+/// and by `FontFileMeasurer`. Text is measured from the left end of its baseline;
+/// `bounds` returns the advance and the bounding box of `text`.
+pub trait TextMeasurer {
+    fn has_font(&self, font_name: &str) -> bool;
+    fn metrics(&self, font_name: &str, size: Real) -> Option<FontMetrics>;
+    fn bounds(&self, font_name: &str, size: Real, letter_spacing: Real, text: &str) -> Option<(Real, BoundingBox)>;
+
+    /// Advances of the prefixes of `text` that end after each of its chars. The provided
+    /// implementation measures every char alone and together with the previous one, which
+    /// keeps the kerning of the pair, so it takes two short measurements per char.
+    fn prefix_advances(&self, font_name: &str, size: Real, letter_spacing: Real, text: &str) -> Option<Vec<Real>> {
+        let mut advances = Vec::with_capacity(text.len());
+        let mut total = 0.0;
+        let mut prev: Option<(usize, Real)> = None;
+        for (idx, ch) in text.char_indices() {
+            let end = idx + ch.len_utf8();
+            let (advance, _) = self.bounds(font_name, size, letter_spacing, &text[idx..end])?;
+            total += match prev {
+                Some((prev_idx, prev_advance)) => {
+                    let (pair_advance, _) = self.bounds(font_name, size, letter_spacing, &text[prev_idx..end])?;
+                    pair_advance - prev_advance
+                },
+                None => advance,
+            };
+            advances.push(total);
+            prev = Some((idx, advance));
+        }
+        Some(advances)
+    }
+}
+
+impl<'f> TextMeasurer for Frame<'f> {
+    fn has_font(&self, font_name: &str) -> bool {
+        NanovgFont::find(self.context(), font_name).is_ok()
+    }
+
+    fn metrics(&self, font_name: &str, size: Real) -> Option<FontMetrics> {
+        let font = NanovgFont::find(self.context(), font_name).ok()?;
+        let metrics = self.text_metrics(font, text_options(size, 0.0));
+
+        Some(FontMetrics {
+            ascender: metrics.ascender,
+            descender: metrics.descender,
+            line_height: metrics.line_height,
+        })
+    }
+
+    fn bounds(&self, font_name: &str, size: Real, letter_spacing: Real, text: &str) -> Option<(Real, BoundingBox)> {
+        let font = NanovgFont::find(self.context(), font_name).ok()?;
+        let (advance, bounds) = self.text_bounds(font, (0.0, 0.0), text, text_options(size, letter_spacing));
+
+        Some((advance, BoundingBox {
+            min_x: bounds.min_x,
+            min_y: bounds.min_y,
+            max_x: bounds.max_x,
+            max_y: bounds.max_y,
+        }))
+    }
+}
+
+fn text_options(size: Real, letter_spacing: Real) -> TextOptions {
+    TextOptions {
+        size,
+        letter_spacing,
+        align: Alignment::new().left().baseline(),
+        ..Default::default()
+    }
+}
+
+/// Measurements of text in one font. A font the measurer doesn't have measures as empty
+/// text at the pen position, as the draw pass skips the runs in it.
+pub(crate) struct Measure<'a> {
+    measurer: &'a dyn TextMeasurer,
+    font_name: &'a str,
+    size: Real,
+    letter_spacing: Real,
+}
+
+impl<'a> Measure<'a> {
+    pub(crate) fn new(measurer: &'a dyn TextMeasurer, font_name: &'a str, size: Real) -> Self {
+        Self { measurer, font_name, size, letter_spacing: 0.0 }
+    }
+
+    pub(crate) fn with_letter_spacing(mut self, letter_spacing: Real) -> Self {
+        self.letter_spacing = letter_spacing;
+        self
+    }
+
+    pub(crate) fn with_text(measurer: &'a dyn TextMeasurer, text: &'a Text) -> Self {
+        Self::new(measurer, text.font_name.as_str(), text.font_size.val())
+    }
+
+    pub(crate) fn has_font(&self) -> bool {
+        self.measurer.has_font(self.font_name)
+    }
+
+    fn bounds(&self, text: &str) -> (Real, BoundingBox) {
+        count_measurement();
+        self.measurer.bounds(self.font_name, self.size, self.letter_spacing, text)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn advance(&self, text: &str) -> Real {
+        self.bounds(text).0
+    }
+
+    pub(crate) fn metrics(&self) -> FontMetrics {
+        count_measurement();
+        self.measurer.metrics(self.font_name, self.size)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn glyph_positions(&self, x: Real, text: &str, align: &AlignHor) -> Vec<GlyphPosition> {
+        count_measurement();
+        let advances = self.measurer.prefix_advances(self.font_name, self.size, self.letter_spacing, text)
+            .unwrap_or_else(|| vec![0.0; text.chars().count()]);
+        let advance = advances.last().cloned().unwrap_or(0.0);
+        let origin_x = match *align {
+            AlignHor::Left => x,
+            AlignHor::Center => x - advance / 2.0,
+            AlignHor::Right => x - advance,
+        };
+
+        let mut min_x = origin_x;
+        text.char_indices().zip(advances)
+            .map(|((byte_index, _), prefix_advance)| {
+                let max_x = origin_x + prefix_advance;
+                let position = GlyphPosition {
+                    byte_index,
+                    min_x,
+                    max_x,
+                };
+                min_x = max_x;
+                position
+            })
+            .collect()
+    }
+
+    pub(crate) fn aligned(&self,
+                          (x, y): (Real, Real),
+                          text: &str,
+                          align_hor: &AlignHor,
+                          align_ver: &AlignVer) -> AlignedText
+    {
+        // nanovg applies the horizontal alignment inconsistently in its bounds functions,
+        // so bounds are measured at the left baseline and then shifted the same way
+        // fontstash shifts the glyphs when drawing.
+        let (advance, text_bounds) = self.bounds(text);
+        let metrics = self.metrics();
+
+        let dx = match *align_hor {
+            AlignHor::Left => 0.0,
+            AlignHor::Center => -advance / 2.0,
+            AlignHor::Right => -advance,
+        };
+        let dy = match *align_ver {
+            AlignVer::Top => metrics.ascender,
+            AlignVer::Middle => (metrics.ascender + metrics.descender) / 2.0,
+            AlignVer::Baseline => 0.0,
+            AlignVer::Bottom => metrics.descender,
+        };
+
+        let (origin_x, origin_y) = (x + dx, y + dy);
+        AlignedText {
+            origin: (origin_x, origin_y),
+            advance,
+            bound: BoundingBox {
+                min_x: origin_x + text_bounds.min_x,
+                min_y: origin_y + text_bounds.min_y,
+                max_x: origin_x + text_bounds.max_x,
+                max_y: origin_y + text_bounds.max_y,
+            },
+        }
+    }
+
+    pub(crate) fn aligned_bounds(&self,
+                                 (x, y): (Real, Real),
+                                 text: &str,
+                                 align_hor: &AlignHor,
+                                 align_ver: &AlignVer) -> BoundingBox
+    {
+        self.aligned((x, y), text, align_hor, align_ver).bound
+    }
+
+    pub(crate) fn word_bounds(measurer: &'a dyn TextMeasurer, text: &'a Text, word: &str) -> BoundingBox {
+        let bound = Self::with_text(measurer, text)
+            .aligned_bounds((text.x.val(), text.y.val()), word, &text.align.0, &text.align.1);
+
+        match text.transform {
+            Some(ref transform) => bound.transform(&transform.matrix),
+            None => bound,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{FONT, measurer, assert_close};
+
+    /// Measures through `bounds` only, so it uses the provided `prefix_advances`.
+    struct BoundsOnly<'a>(&'a dyn TextMeasurer);
+
+    impl<'a> TextMeasurer for BoundsOnly<'a> {
+        fn has_font(&self, font_name: &str) -> bool {
+            self.0.has_font(font_name)
+        }
+
+        fn metrics(&self, font_name: &str, size: Real) -> Option<FontMetrics> {
+            self.0.metrics(font_name, size)
+        }
+
+        fn bounds(&self, font_name: &str, size: Real, letter_spacing: Real, text: &str) -> Option<(Real, BoundingBox)> {
+            self.0.bounds(font_name, size, letter_spacing, text)
+        }
+    }
+
+    #[test]
+    fn glyph_positions_end_at_prefix_advances() {
+        let measurer = measurer();
+        let measure = Measure::new(&measurer, FONT, 20.0).with_letter_spacing(1.5);
+        let text = "AVATAR Wä";
+
+        let positions = measure.glyph_positions(10.0, text, &AlignHor::Left);
+        assert_eq!(positions.len(), text.chars().count());
+        let mut min_x = 10.0;
+        for (position, (byte_index, ch)) in positions.iter().zip(text.char_indices()) {
+            assert_eq!(position.byte_index, byte_index);
+            assert_eq!(position.min_x, min_x);
+            assert_eq!(position.max_x, 10.0 + measure.advance(&text[..byte_index + ch.len_utf8()]));
+            min_x = position.max_x;
+        }
+
+        let right = measure.glyph_positions(10.0, text, &AlignHor::Right);
+        assert_eq!(right.last().map(|position| position.max_x), Some(10.0));
+    }
+
+    #[test]
+    fn a_missing_font_measures_as_empty_text() {
+        let measurer = measurer();
+        let measure = Measure::new(&measurer, "Missing", 20.0);
+        assert!(!measure.has_font());
+        assert_eq!(measure.advance("Hello"), 0.0);
+        assert_eq!(
+            measure.aligned_bounds((10.0, 20.0), "Hello", &AlignHor::Center, &AlignVer::Middle),
+            BoundingBox { min_x: 10.0, min_y: 20.0, max_x: 10.0, max_y: 20.0 },
+        );
+        let positions = measure.glyph_positions(5.0, "Hi", &AlignHor::Left);
+        let spans: Vec<_> = positions.iter().map(|glyph| (glyph.min_x, glyph.max_x)).collect();
+        assert_eq!(spans, vec![(5.0, 5.0), (5.0, 5.0)]);
+    }
+
+    /// Roboto's "Align" at 24 px advances 42 px. Its glyphs span -1..43 px from the pen:
+    /// the `glyf` boxes of 'A' (xMin 20) and 'n' (xMax 1021 at pen 32) padded by a pixel.
+    /// The lines are 24 px high with the ascender 19.0685 px above the baseline.
+    #[test]
+    fn aligned_bounds_enclose_the_glyphs_at_every_alignment() {
+        let measurer = measurer();
+        let measure = Measure::new(&measurer, FONT, 24.0);
+        let (x, y) = (100.0, 50.0);
+
+        let hors = [
+            (AlignHor::Left, "left", 99.0),
+            (AlignHor::Center, "center", 78.0),
+            (AlignHor::Right, "right", 57.0),
+        ];
+        let vers = [
+            (AlignVer::Top, "top", 50.0),
+            (AlignVer::Middle, "middle", 38.0),
+            (AlignVer::Baseline, "baseline", 30.9315),
+            (AlignVer::Bottom, "bottom", 26.0),
+        ];
+        for &(ref align_hor, hor_name, min_x) in hors.iter() {
+            for &(ref align_ver, ver_name, min_y) in vers.iter() {
+                let what = |edge: &str| format!("{} {} {}", hor_name, ver_name, edge);
+                let aligned = measure.aligned((x, y), "Align", align_hor, align_ver);
+                assert_eq!(aligned.advance, 42.0);
+                assert_close(aligned.bound.min_x, min_x, &what("min_x"));
+                assert_close(aligned.bound.max_x, min_x + 44.0, &what("max_x"));
+                assert_close(aligned.bound.min_y, min_y, &what("min_y"));
+                assert_close(aligned.bound.max_y, min_y + 24.0, &what("max_y"));
+            }
+        }
+    }
+
+    #[test]
+    fn provided_prefix_advances_match_the_font_file_ones() {
+        let measurer = measurer();
+        let text = "AVATAR Wä";
+        let expected = measurer.prefix_advances(FONT, 20.0, 1.5, text);
+        assert!(expected.is_some());
+        assert_eq!(BoundsOnly(&measurer).prefix_advances(FONT, 20.0, 1.5, text), expected);
+        assert_eq!(BoundsOnly(&measurer).prefix_advances("Missing", 20.0, 0.0, text), None);
+    }
+}
@@ -0,0 +1,562 @@
+use std::collections::HashMap;
+use exgui::{Real, PathCommand, Stroke, LineCap, LineJoin};
+use crate::{BoundingBox, NodePath};
+
+/// Matrices mapping the commands of `Path` nodes with `PathUnits` onto their parent bound,
+/// resolved by the recalc pass. They map the points only, strokes keep their widths.
+pub(crate) type PathLayouts = HashMap<NodePath, [Real; 6]>;
+
+pub(crate) type Point = (Real, Real);
+
+/// A path command with absolute coordinates. `Unsupported` keeps the index of a command
+/// that can't be drawn yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Segment {
+    Move(Point),
+    Line(Point),
+    Quad(Point, Point),
+    Cubic(Point, Point, Point),
+    Close,
+    Unsupported(usize),
+}
+
+/// Resolves relative commands and bézier control points. Quadratic curves take the last
+/// control point, cubic ones the last two.
+pub(crate) fn segments(cmds: &[PathCommand]) -> Vec<Segment> {
+    use exgui::PathCommand::*;
+
+    let mut last_xy = [0.0_f32, 0.0];
+    let mut bez_ctrls = [(0.0_f32, 0.0), (0.0_f32, 0.0)];
+    let mut segments = Vec::with_capacity(cmds.len());
+
+    for (idx, cmd) in cmds.iter().enumerate() {
+        let segment = match cmd {
+            Move(ref xy) => {
+                last_xy = *xy;
+                Segment::Move((last_xy[0], last_xy[1]))
+            },
+            MoveRel(ref xy) => {
+                last_xy = [last_xy[0] + xy[0], last_xy[1] + xy[1]];
+                Segment::Move((last_xy[0], last_xy[1]))
+            },
+            Line(ref xy) => {
+                last_xy = *xy;
+                Segment::Line((last_xy[0], last_xy[1]))
+            },
+            LineRel(ref xy) => {
+                last_xy = [last_xy[0] + xy[0], last_xy[1] + xy[1]];
+                Segment::Line((last_xy[0], last_xy[1]))
+            },
+            LineAlonX(ref x) => {
+                last_xy[0] = *x;
+                Segment::Line((last_xy[0], last_xy[1]))
+            },
+            LineAlonXRel(ref x) => {
+                last_xy[0] += *x;
+                Segment::Line((last_xy[0], last_xy[1]))
+            },
+            LineAlonY(ref y) => {
+                last_xy[1] = *y;
+                Segment::Line((last_xy[0], last_xy[1]))
+            },
+            LineAlonYRel(ref y) => {
+                last_xy[1] += *y;
+                Segment::Line((last_xy[0], last_xy[1]))
+            },
+            Close => Segment::Close,
+            BezCtrl(ref xy) => {
+                bez_ctrls = [bez_ctrls[1], (xy[0], xy[1])];
+                continue;
+            },
+            BezCtrlRel(ref xy) => {
+                bez_ctrls = [bez_ctrls[1], (last_xy[0] + xy[0], last_xy[1] + xy[1])];
+                continue;
+            },
+            QuadBezTo(ref xy) => {
+                last_xy = *xy;
+                Segment::Quad(bez_ctrls[1], (last_xy[0], last_xy[1]))
+            },
+            QuadBezToRel(ref xy) => {
+                last_xy = [last_xy[0] + xy[0], last_xy[1] + xy[1]];
+                Segment::Quad(bez_ctrls[1], (last_xy[0], last_xy[1]))
+            },
+            CubBezTo(ref xy) => {
+                last_xy = *xy;
+                Segment::Cubic(bez_ctrls[0], bez_ctrls[1], (last_xy[0], last_xy[1]))
+            },
+            CubBezToRel(ref xy) => {
+                last_xy = [last_xy[0] + xy[0], last_xy[1] + xy[1]];
+                Segment::Cubic(bez_ctrls[0], bez_ctrls[1], (last_xy[0], last_xy[1]))
+            },
+            _ => Segment::Unsupported(idx),
+        };
+        segments.push(segment);
+    }
+    segments
+}
+
+/// `matrix` applied after `other`.
+pub(crate) fn multiply(matrix: &[Real; 6], other: &[Real; 6]) -> [Real; 6] {
+    let (m, n) = (matrix, other);
+    [
+        m[0] * n[0] + m[2] * n[1],
+        m[1] * n[0] + m[3] * n[1],
+        m[0] * n[2] + m[2] * n[3],
+        m[1] * n[2] + m[3] * n[3],
+        m[0] * n[4] + m[2] * n[5] + m[4],
+        m[1] * n[4] + m[3] * n[5] + m[5],
+    ]
+}
+
+/// `None` for matrices that collapse the plane.
+pub(crate) fn invert(matrix: &[Real; 6]) -> Option<[Real; 6]> {
+    let [a, b, c, d, e, f] = *matrix;
+    let det = a * d - b * c;
+    if det == 0.0 {
+        return None;
+    }
+    Some([
+        d / det,
+        -b / det,
+        -c / det,
+        a / det,
+        (c * f - d * e) / det,
+        (b * e - a * f) / det,
+    ])
+}
+
+pub(crate) fn apply(matrix: &[Real; 6], (x, y): Point) -> Point {
+    (matrix[0] * x + matrix[2] * y + matrix[4], matrix[1] * x + matrix[3] * y + matrix[5])
+}
+
+pub(crate) const IDENTITY: [Real; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// The segments with their points mapped through `matrix`. Unlike a transform of the drawn
+/// path, this leaves the stroke width as it is.
+pub(crate) fn map_segments(segments: &[Segment], matrix: &[Real; 6]) -> Vec<Segment> {
+    segments.iter()
+        .map(|segment| match *segment {
+            Segment::Move(to) => Segment::Move(apply(matrix, to)),
+            Segment::Line(to) => Segment::Line(apply(matrix, to)),
+            Segment::Quad(ctrl, to) => Segment::Quad(apply(matrix, ctrl), apply(matrix, to)),
+            Segment::Cubic(ctrl1, ctrl2, to) => {
+                Segment::Cubic(apply(matrix, ctrl1), apply(matrix, ctrl2), apply(matrix, to))
+            },
+            segment => segment,
+        })
+        .collect()
+}
+
+/// Bezier circle approximation constant used by nanovg.
+const KAPPA: Real = 0.552_284_8;
+
+/// Outline of `Rect` as nanovg draws it.
+pub(crate) fn rect_segments(rect: &BoundingBox) -> Vec<Segment> {
+    vec![
+        Segment::Move((rect.min_x, rect.min_y)),
+        Segment::Line((rect.min_x, rect.max_y)),
+        Segment::Line((rect.max_x, rect.max_y)),
+        Segment::Line((rect.max_x, rect.min_y)),
+        Segment::Close,
+    ]
+}
+
+/// Outline of `Circle` as nanovg draws it: four cubic curves.
+pub(crate) fn circle_segments((cx, cy): Point, r: Real) -> Vec<Segment> {
+    let k = r * KAPPA;
+    vec![
+        Segment::Move((cx - r, cy)),
+        Segment::Cubic((cx - r, cy + k), (cx - k, cy + r), (cx, cy + r)),
+        Segment::Cubic((cx + k, cy + r), (cx + r, cy + k), (cx + r, cy)),
+        Segment::Cubic((cx + r, cy - k), (cx + k, cy - r), (cx, cy - r)),
+        Segment::Cubic((cx - k, cy - r), (cx - r, cy - k), (cx - r, cy)),
+        Segment::Close,
+    ]
+}
+
+/// Exact bound of the segments mapped through `matrix`, with the extrema of the curves and,
+/// with `stroke`, the stroke outline of its caps and joins. Curves stay curves of the mapped
+/// control points, so the extrema are found after mapping. As in nanovg, the stroke width is
+/// scaled by the average scale of `matrix`. `None` for paths that draw nothing.
+pub(crate) fn bound(segments: &[Segment], matrix: &[Real; 6], stroke: Option<&Stroke>) -> Option<BoundingBox> {
+    let outline = stroke.map(|stroke| Outline {
+        half_width: stroke.width * average_scale(matrix) / 2.0,
+        line_cap: stroke.line_cap,
+        line_join: stroke.line_join,
+        miter_limit: stroke.miter_limit,
+    });
+    let half_width = outline.as_ref().map_or(0.0, |outline| outline.half_width);
+
+    let mut extent = Extent::default();
+    let mut pieces = Vec::new();
+    let mut start = apply(matrix, (0.0, 0.0));
+    let mut current = start;
+    for segment in segments {
+        match *segment {
+            Segment::Move(to) => {
+                subpath(outline.as_ref(), &pieces, false, &mut extent);
+                pieces.clear();
+                current = apply(matrix, to);
+                start = current;
+            },
+            Segment::Line(to) => {
+                let to = apply(matrix, to);
+                pieces.extend(Piece::new(current, to, sub(to, current), sub(to, current)));
+                current = to;
+            },
+            Segment::Quad(ctrl, to) => {
+                let (ctrl, to) = (apply(matrix, ctrl), apply(matrix, to));
+                let [x_root, y_root] = quad_extrema(current, ctrl, to);
+                for (root, axis) in [(x_root, Axis::X), (y_root, Axis::Y)].iter() {
+                    if let Some(t) = *root {
+                        extent.add_extremum(quad_point(current, ctrl, to, t), *axis, half_width);
+                    }
+                }
+                let chord = sub(to, current);
+                let (from_dir, to_dir) = (direction(sub(ctrl, current), chord), direction(sub(to, ctrl), chord));
+                pieces.extend(Piece::new(current, to, from_dir, to_dir));
+                current = to;
+            },
+            Segment::Cubic(ctrl1, ctrl2, to) => {
+                let (ctrl1, ctrl2, to) = (apply(matrix, ctrl1), apply(matrix, ctrl2), apply(matrix, to));
+                let [x1, x2, y1, y2] = cubic_extrema(current, ctrl1, ctrl2, to);
+                for (root, axis) in [(x1, Axis::X), (x2, Axis::X), (y1, Axis::Y), (y2, Axis::Y)].iter() {
+                    if let Some(t) = *root {
+                        extent.add_extremum(cubic_point(current, ctrl1, ctrl2, to, t), *axis, half_width);
+                    }
+                }
+                let chord = sub(to, current);
+                let from_dir = direction(sub(ctrl1, current), direction(sub(ctrl2, current), chord));
+                let to_dir = direction(sub(to, ctrl2), direction(sub(to, ctrl1), chord));
+                pieces.extend(Piece::new(current, to, from_dir, to_dir));
+                current = to;
+            },
+            Segment::Close => {
+                subpath(outline.as_ref(), &pieces, true, &mut extent);
+                pieces.clear();
+                current = start;
+            },
+            Segment::Unsupported(_) => (),
+        }
+    }
+    subpath(outline.as_ref(), &pieces, false, &mut extent);
+    extent.bound
+}
+
+fn subpath(outline: Option<&Outline>, pieces: &[Piece], closed: bool, extent: &mut Extent) {
+    match outline {
+        Some(outline) => outline.subpath(pieces, closed, extent),
+        None => pieces.iter().for_each(|piece| piece.ends(0.0, extent)),
+    }
+}
+
+fn average_scale(matrix: &[Real; 6]) -> Real {
+    let scale_x = (matrix[0] * matrix[0] + matrix[2] * matrix[2]).sqrt();
+    let scale_y = (matrix[1] * matrix[1] + matrix[3] * matrix[3]).sqrt();
+    (scale_x + scale_y) / 2.0
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+/// `dir`, or `fallback` when `dir` is zero, as for curves with control points at their ends.
+fn direction(dir: Point, fallback: Point) -> Point {
+    if dir == (0.0, 0.0) { fallback } else { dir }
+}
+
+/// Unit normal of the direction `dir`.
+fn normal(dir: Point) -> Point {
+    let len = (dir.0 * dir.0 + dir.1 * dir.1).sqrt();
+    (-dir.1 / len, dir.0 / len)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    X,
+    Y,
+}
+
+#[derive(Debug, Default)]
+struct Extent {
+    bound: Option<BoundingBox>,
+}
+
+impl Extent {
+    fn add(&mut self, (x, y): Point) {
+        let point = BoundingBox { min_x: x, min_y: y, max_x: x, max_y: y };
+        self.bound = Some(self.bound.map_or(point, |bound| bound.union(&point)));
+    }
+
+    fn add_disc(&mut self, (x, y): Point, radius: Real) {
+        self.add((x - radius, y - radius));
+        self.add((x + radius, y + radius));
+    }
+
+    /// At an extremum of a curve on `axis` its stroke reaches `half_width` further on that axis.
+    fn add_extremum(&mut self, (x, y): Point, axis: Axis, half_width: Real) {
+        match axis {
+            Axis::X => {
+                self.add((x - half_width, y));
+                self.add((x + half_width, y));
+            },
+            Axis::Y => {
+                self.add((x, y - half_width));
+                self.add((x, y + half_width));
+            },
+        }
+    }
+}
+
+/// A drawn segment with the directions at its ends.
+#[derive(Debug, Clone, Copy)]
+struct Piece {
+    from: Point,
+    to: Point,
+    from_dir: Point,
+    to_dir: Point,
+}
+
+impl Piece {
+    /// Zero length pieces have no direction and draw nothing.
+    fn new(from: Point, to: Point, from_dir: Point, to_dir: Point) -> Option<Self> {
+        if from == to && (from_dir == (0.0, 0.0) || to_dir == (0.0, 0.0)) {
+            None
+        } else {
+            Some(Piece { from, to, from_dir, to_dir })
+        }
+    }
+
+    fn ends(&self, half_width: Real, extent: &mut Extent) {
+        for &(point, dir) in [(self.from, self.from_dir), (self.to, self.to_dir)].iter() {
+            let (nx, ny) = normal(dir);
+            extent.add((point.0 + nx * half_width, point.1 + ny * half_width));
+            extent.add((point.0 - nx * half_width, point.1 - ny * half_width));
+        }
+    }
+}
+
+struct Outline {
+    half_width: Real,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    miter_limit: Real,
+}
+
+impl Outline {
+    /// Adds the stroke outline of a subpath: the ends of its pieces, the joins between them
+    /// and the caps of open subpaths. Closed subpaths are joined back to their start.
+    fn subpath(&self, pieces: &[Piece], closed: bool, extent: &mut Extent) {
+        let (first, last) = match (pieces.first(), pieces.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return,
+        };
+
+        let mut pieces = pieces.to_vec();
+        if closed && last.to != first.from {
+            let closing = sub(first.from, last.to);
+            pieces.push(Piece { from: last.to, to: first.from, from_dir: closing, to_dir: closing });
+        }
+        for piece in pieces.iter() {
+            piece.ends(self.half_width, extent);
+        }
+        for pair in pieces.windows(2) {
+            self.join(pair[0].to, pair[0].to_dir, pair[1].from_dir, extent);
+        }
+
+        if closed {
+            if let Some(last) = pieces.last() {
+                self.join(last.to, last.to_dir, first.from_dir, extent);
+            }
+        } else {
+            self.cap(first.from, (-first.from_dir.0, -first.from_dir.1), extent);
+            self.cap(last.to, last.to_dir, extent);
+        }
+    }
+
+    /// Miter joins beyond the miter limit are beveled, as in nanovg.
+    fn join(&self, point: Point, dir_in: Point, dir_out: Point, extent: &mut Extent) {
+        match self.line_join {
+            LineJoin::Round => extent.add_disc(point, self.half_width),
+            LineJoin::Miter => {
+                let (n_in, n_out) = (normal(dir_in), normal(dir_out));
+                let miter = (n_in.0 + n_out.0, n_in.1 + n_out.1);
+                let len = (miter.0 * miter.0 + miter.1 * miter.1).sqrt();
+                let cross = dir_in.0 * dir_out.1 - dir_in.1 * dir_out.0;
+                if len == 0.0 || cross == 0.0 {
+                    return;
+                }
+                let cos_half = (miter.0 * n_in.0 + miter.1 * n_in.1) / len;
+                if cos_half <= 0.0 || 1.0 / cos_half > self.miter_limit {
+                    return;
+                }
+                let tip = -cross.signum() * self.half_width / (cos_half * len);
+                extent.add((point.0 + miter.0 * tip, point.1 + miter.1 * tip));
+            },
+            LineJoin::Bevel => (),
+        }
+    }
+
+    /// Cap at the end `point` of a subpath going out in `dir`.
+    fn cap(&self, point: Point, dir: Point, extent: &mut Extent) {
+        match self.line_cap {
+            LineCap::Butt => (),
+            LineCap::Round => extent.add_disc(point, self.half_width),
+            LineCap::Square => {
+                let (nx, ny) = normal(dir);
+                let (tx, ty) = (ny, -nx);
+                let hw = self.half_width;
+                extent.add((point.0 + (tx + nx) * hw, point.1 + (ty + ny) * hw));
+                extent.add((point.0 + (tx - nx) * hw, point.1 + (ty - ny) * hw));
+            },
+        }
+    }
+}
+
+fn quad_point(p0: Point, p1: Point, p2: Point, t: Real) -> Point {
+    let mt = 1.0 - t;
+    let at = |a: Real, b: Real, c: Real| mt * mt * a + 2.0 * mt * t * b + t * t * c;
+    (at(p0.0, p1.0, p2.0), at(p0.1, p1.1, p2.1))
+}
+
+fn cubic_point(p0: Point, p1: Point, p2: Point, p3: Point, t: Real) -> Point {
+    let mt = 1.0 - t;
+    let at = |a: Real, b: Real, c: Real, d: Real| {
+        mt * mt * mt * a + 3.0 * mt * mt * t * b + 3.0 * mt * t * t * c + t * t * t * d
+    };
+    (at(p0.0, p1.0, p2.0, p3.0), at(p0.1, p1.1, p2.1, p3.1))
+}
+
+/// Parameters inside the curve where the derivative of x or y is zero.
+fn quad_extrema(p0: Point, p1: Point, p2: Point) -> [Option<Real>; 2] {
+    let root = |a: Real, b: Real, c: Real| {
+        let denom = a - 2.0 * b + c;
+        if denom == 0.0 { None } else { inner((a - b) / denom) }
+    };
+    [root(p0.0, p1.0, p2.0), root(p0.1, p1.1, p2.1)]
+}
+
+fn cubic_extrema(p0: Point, p1: Point, p2: Point, p3: Point) -> [Option<Real>; 4] {
+    let [x1, x2] = derivative_roots(p0.0, p1.0, p2.0, p3.0);
+    let [y1, y2] = derivative_roots(p0.1, p1.1, p2.1, p3.1);
+    [x1, x2, y1, y2]
+}
+
+/// Roots of the derivative `a t² + b t + c` of a cubic coordinate, divided by 3.
+fn derivative_roots(p0: Real, p1: Real, p2: Real, p3: Real) -> [Option<Real>; 2] {
+    let a = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+    let b = 2.0 * (p0 - 2.0 * p1 + p2);
+    let c = p1 - p0;
+
+    if a.abs() < 1e-6 {
+        return [if b == 0.0 { None } else { inner(-c / b) }, None];
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return [None, None];
+    }
+    let sqrt = discriminant.sqrt();
+    [inner((-b + sqrt) / (2.0 * a)), inner((-b - sqrt) / (2.0 * a))]
+}
+
+fn inner(t: Real) -> Option<Real> {
+    if t > 0.0 && t < 1.0 { Some(t) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::assert_close;
+
+    fn stroke(width: Real, line_cap: LineCap, line_join: LineJoin, miter_limit: Real) -> Stroke {
+        Stroke { width, line_cap, line_join, miter_limit, ..Default::default() }
+    }
+
+    fn assert_bound(bound: Option<BoundingBox>, (min_x, min_y, max_x, max_y): (Real, Real, Real, Real), what: &str) {
+        let bound = bound.unwrap_or_else(|| panic!("{} must have a bound", what));
+        assert_close(bound.min_x, min_x, &format!("{} min_x", what));
+        assert_close(bound.min_y, min_y, &format!("{} min_y", what));
+        assert_close(bound.max_x, max_x, &format!("{} max_x", what));
+        assert_close(bound.max_y, max_y, &format!("{} max_y", what));
+    }
+
+    #[test]
+    fn inverts_matrices_that_keep_the_plane() {
+        let matrix = [2.0, 0.0, 0.0, 4.0, 10.0, 20.0];
+        let inverse = invert(&matrix).expect("The matrix must be invertible");
+        assert_eq!(inverse, [0.5, 0.0, 0.0, 0.25, -5.0, -5.0]);
+        assert_eq!(multiply(&matrix, &inverse), IDENTITY);
+        assert_eq!(apply(&inverse, (12.0, 24.0)), (1.0, 1.0));
+
+        assert_eq!(invert(&[1.0, 2.0, 2.0, 4.0, 5.0, 6.0]), None);
+    }
+
+    #[test]
+    fn maps_the_points_of_the_segments() {
+        let matrix = [2.0, 0.0, 0.0, 3.0, 1.0, 1.0];
+        let segments = [
+            Segment::Move((1.0, 1.0)),
+            Segment::Line((2.0, 0.0)),
+            Segment::Quad((0.0, 1.0), (1.0, 2.0)),
+            Segment::Cubic((0.0, 0.0), (1.0, 0.0), (2.0, 2.0)),
+            Segment::Close,
+            Segment::Unsupported(5),
+        ];
+        assert_eq!(map_segments(&segments, &matrix), vec![
+            Segment::Move((3.0, 4.0)),
+            Segment::Line((5.0, 1.0)),
+            Segment::Quad((1.0, 4.0), (3.0, 7.0)),
+            Segment::Cubic((1.0, 1.0), (3.0, 1.0), (5.0, 7.0)),
+            Segment::Close,
+            Segment::Unsupported(5),
+        ]);
+    }
+
+    /// The y of the cubic peaks at t = 0.5, at 3/4 of the height of its control points.
+    #[test]
+    fn bounds_a_cubic_at_its_inner_extremum() {
+        let segments = [Segment::Move((0.0, 0.0)), Segment::Cubic((0.0, 10.0), (10.0, 10.0), (10.0, 0.0))];
+        assert_bound(bound(&segments, &IDENTITY, None), (0.0, 0.0, 10.0, 7.5), "The fill");
+
+        let stroke = stroke(2.0, LineCap::Butt, LineJoin::Miter, 10.0);
+        assert_bound(bound(&segments, &IDENTITY, Some(&stroke)), (-1.0, 0.0, 11.0, 8.5), "The stroke");
+    }
+
+    #[test]
+    fn caps_extend_the_open_ends() {
+        let line = [Segment::Move((0.0, 0.0)), Segment::Line((10.0, 0.0))];
+        let diagonal = [Segment::Move((0.0, 0.0)), Segment::Line((10.0, 10.0))];
+        let half_diagonal = 0.5_f32.sqrt();
+        let cases = [
+            (LineCap::Butt, "butt", (0.0, -1.0, 10.0, 1.0), 10.0 + half_diagonal),
+            (LineCap::Square, "square", (-1.0, -1.0, 11.0, 1.0), 10.0 + 2.0 * half_diagonal),
+            (LineCap::Round, "round", (-1.0, -1.0, 11.0, 1.0), 11.0),
+        ];
+        for &(line_cap, name, expected, diagonal_max) in cases.iter() {
+            let stroke = stroke(2.0, line_cap, LineJoin::Miter, 10.0);
+            assert_bound(bound(&line, &IDENTITY, Some(&stroke)), expected, name);
+            let diagonal_bound = bound(&diagonal, &IDENTITY, Some(&stroke)).expect("The diagonal must have a bound");
+            assert_close(diagonal_bound.max_x, diagonal_max, &format!("{} diagonal max_x", name));
+            assert_close(diagonal_bound.max_y, diagonal_max, &format!("{} diagonal max_y", name));
+        }
+    }
+
+    /// A right angle join has a miter ratio of √2: its tip reaches √2 half widths past the
+    /// corner, its bevel half of that.
+    #[test]
+    fn miters_joins_up_to_the_miter_limit_and_bevels_beyond() {
+        let segments = [Segment::Move((0.0, 0.0)), Segment::Line((10.0, 10.0)), Segment::Line((20.0, 0.0))];
+        let sqrt_2 = 2.0_f32.sqrt();
+
+        let below_limit = stroke(2.0, LineCap::Butt, LineJoin::Miter, sqrt_2 + 0.01);
+        let mitered = bound(&segments, &IDENTITY, Some(&below_limit)).expect("The path must have a bound");
+        assert_close(mitered.max_y, 10.0 + sqrt_2, "The miter tip");
+
+        let above_limit = stroke(2.0, LineCap::Butt, LineJoin::Miter, sqrt_2 - 0.01);
+        let beveled = bound(&segments, &IDENTITY, Some(&above_limit)).expect("The path must have a bound");
+        assert_close(beveled.max_y, 10.0 + sqrt_2 / 2.0, "The bevel");
+
+        let bevel = stroke(2.0, LineCap::Butt, LineJoin::Bevel, 10.0);
+        assert_eq!(bound(&segments, &IDENTITY, Some(&bevel)), Some(beveled));
+    }
+}
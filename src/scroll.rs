@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use exgui::Real;
+use crate::{BoundingBox, NodePath};
+use crate::style::Scrollbars;
+
+pub(crate) type ScrollLayouts = HashMap<NodePath, ScrollExtent>;
+
+/// Bound of a scrolled viewport and of its content: the viewport together with
+/// the bounds of its children.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollExtent {
+    pub viewport: BoundingBox,
+    pub content: BoundingBox,
+}
+
+impl ScrollExtent {
+    /// Offsets that keep the viewport inside the content, negative for content
+    /// above or left of the viewport.
+    pub fn offset_range(&self) -> ((Real, Real), (Real, Real)) {
+        (
+            (self.content.min_x - self.viewport.min_x, self.content.min_y - self.viewport.min_y),
+            (self.content.max_x - self.viewport.max_x, self.content.max_y - self.viewport.max_y),
+        )
+    }
+
+    pub fn clamp(&self, (x, y): (Real, Real)) -> (Real, Real) {
+        let ((min_x, min_y), (max_x, max_y)) = self.offset_range();
+        (x.max(min_x).min(max_x), y.max(min_y).min(max_y))
+    }
+
+    /// Thumbs of the overflowing axes at the clamped `offset`. When both axes overflow
+    /// the tracks stop short of the corner they share.
+    pub(crate) fn thumbs(&self, offset: (Real, Real), scrollbars: &Scrollbars) -> Vec<BoundingBox> {
+        let (offset_x, offset_y) = self.clamp(offset);
+        let ((min_x, min_y), (max_x, max_y)) = self.offset_range();
+        let viewport = &self.viewport;
+        let width = scrollbars.width;
+        let overflow_x = max_x > min_x;
+        let overflow_y = max_y > min_y;
+
+        let thumb = |track_start: Real, track_length: Real, visible_fraction: Real, position: Real| {
+            let length = (track_length * visible_fraction).max(scrollbars.min_thumb_length).min(track_length);
+            let start = track_start + (track_length - length) * position;
+            (start, start + length)
+        };
+
+        let mut thumbs = Vec::new();
+        if overflow_y {
+            let track = viewport.height() - if overflow_x { width } else { 0.0 };
+            let fraction = viewport.height() / self.content.height();
+            let (start, end) = thumb(viewport.min_y, track, fraction, (offset_y - min_y) / (max_y - min_y));
+            thumbs.push(BoundingBox {
+                min_x: viewport.max_x - width,
+                min_y: start,
+                max_x: viewport.max_x,
+                max_y: end,
+            });
+        }
+        if overflow_x {
+            let track = viewport.width() - if overflow_y { width } else { 0.0 };
+            let fraction = viewport.width() / self.content.width();
+            let (start, end) = thumb(viewport.min_x, track, fraction, (offset_x - min_x) / (max_x - min_x));
+            thumbs.push(BoundingBox {
+                min_x: start,
+                min_y: viewport.max_y - width,
+                max_x: end,
+                max_y: viewport.max_y,
+            });
+        }
+        thumbs
+    }
+}
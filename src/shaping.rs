@@ -0,0 +1,409 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Content of a `Word` as nanovg draws and measures it, with the logical order of its chars.
+pub(crate) struct Shaped<'a> {
+    pub content: Cow<'a, str>,
+    pub order: Order,
+}
+
+/// A drawn char and the logical content it comes from: one char, or two for a ligature.
+#[derive(Debug, Clone)]
+struct Cluster {
+    logical: Range<usize>,
+    visual: Range<usize>,
+    rtl: bool,
+}
+
+/// Maps byte indices between the logical content of a `Word` and the shaped one.
+/// Without clusters, e.g. for content that needs no shaping, both are the same.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Order {
+    clusters: Vec<Cluster>,
+}
+
+impl Order {
+    /// Shaped index of the caret before the logical `index`: at the left edge of
+    /// a left-to-right char and at the right edge of a right-to-left one.
+    pub(crate) fn visual_caret(&self, index: usize) -> usize {
+        let edge = |cluster: &Cluster, before: bool| if cluster.rtl == before {
+            cluster.visual.end
+        } else {
+            cluster.visual.start
+        };
+        match self.clusters.iter().find(|cluster| cluster.logical.contains(&index)) {
+            Some(cluster) => edge(cluster, true),
+            None => self.clusters.iter()
+                .max_by_key(|cluster| cluster.logical.end)
+                .map_or(index, |cluster| edge(cluster, false)),
+        }
+    }
+
+    /// Logical index of the caret at the shaped `index`, a boundary between drawn chars.
+    pub(crate) fn logical_caret(&self, index: usize) -> usize {
+        if let Some(cluster) = self.clusters.iter().find(|cluster| cluster.visual.start == index) {
+            return if cluster.rtl { cluster.logical.end } else { cluster.logical.start };
+        }
+        self.clusters.iter()
+            .find(|cluster| cluster.visual.end == index)
+            .map_or(index, |cluster| if cluster.rtl { cluster.logical.start } else { cluster.logical.end })
+    }
+
+    /// Logical index of the drawn char starting at the shaped `index`.
+    pub(crate) fn logical_index(&self, index: usize) -> usize {
+        self.clusters.iter()
+            .find(|cluster| cluster.visual.start == index)
+            .map_or(index, |cluster| cluster.logical.start)
+    }
+
+    /// Shaped ranges, left to right, of the chars of the logical range `start..end`.
+    pub(crate) fn visual_ranges(&self, start: usize, end: usize) -> Vec<Range<usize>> {
+        if self.clusters.is_empty() {
+            return vec![start..end];
+        }
+
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for cluster in self.clusters.iter().filter(|cluster| (start..end).contains(&cluster.logical.start)) {
+            match ranges.last_mut() {
+                Some(last) if last.end == cluster.visual.start => last.end = cluster.visual.end,
+                _ => ranges.push(cluster.visual.clone()),
+            }
+        }
+        ranges
+    }
+}
+
+/// Converts the logical content of a `Word` into the string nanovg should draw and measure.
+pub(crate) fn shape(content: &str) -> Cow<str> {
+    shaped(content).content
+}
+
+/// Without the `shaping` feature the content is used as is.
+#[cfg(not(feature = "shaping"))]
+pub(crate) fn shaped(content: &str) -> Shaped {
+    Shaped { content: Cow::Borrowed(content), order: Order::default() }
+}
+
+/// Arabic letters are replaced with their contextual presentation forms and the result
+/// is reordered into visual order by the Unicode bidirectional algorithm.
+///
+/// This is not a complete shaper: Indic and other scripts that need glyph substitution
+/// and reordering from the font (conjuncts, pre-base vowel signs) are drawn unshaped,
+/// one codepoint after another.
+#[cfg(feature = "shaping")]
+pub(crate) fn shaped(content: &str) -> Shaped {
+    use unicode_bidi::BidiInfo;
+
+    if !content.chars().any(is_rtl) {
+        return Shaped { content: Cow::Borrowed(content), order: Order::default() };
+    }
+
+    let (joined, sources) = arabic::join(content);
+    let info = BidiInfo::new(&joined, None);
+    let mut visual = String::with_capacity(joined.len());
+    let mut clusters = Vec::with_capacity(sources.len());
+    for para in info.paragraphs.iter() {
+        let (levels, runs) = info.visual_runs(para, para.range.clone());
+        for run in runs {
+            let rtl = levels[run.start].is_rtl();
+            let mut chars: Vec<_> = joined[run.clone()].char_indices()
+                .map(|(idx, ch)| (run.start + idx, ch))
+                .collect();
+            if rtl {
+                chars.reverse();
+            }
+            for (joined_idx, ch) in chars {
+                let logical = sources.binary_search_by_key(&joined_idx, |&(idx, _)| idx)
+                    .map_or(joined_idx..joined_idx + ch.len_utf8(), |found| sources[found].1.clone());
+                let start = visual.len();
+                visual.push(ch);
+                clusters.push(Cluster { logical, visual: start..visual.len(), rtl });
+            }
+        }
+    }
+    Shaped { content: Cow::Owned(visual), order: Order { clusters } }
+}
+
+/// Without the `shaping` feature the runs of a line are drawn in logical order.
+#[cfg(not(feature = "shaping"))]
+pub(crate) fn visual_order(runs: &[&str]) -> Vec<usize> {
+    (0..runs.len()).collect()
+}
+
+/// Order, left to right, in which the runs of a flowed line are drawn. `shaped` reorders the
+/// chars inside each run; across the runs, a run takes the lowest bidi level its chars, other
+/// than spaces, have in the whole line, so right-to-left text spanning several words is drawn
+/// from right to left.
+#[cfg(feature = "shaping")]
+pub(crate) fn visual_order(runs: &[&str]) -> Vec<usize> {
+    use unicode_bidi::BidiInfo;
+
+    if !runs.iter().any(|run| run.chars().any(is_rtl)) {
+        return (0..runs.len()).collect();
+    }
+
+    let line = runs.concat();
+    let info = BidiInfo::new(&line, None);
+    let levels = match info.paragraphs.first() {
+        Some(para) => info.reordered_levels(para, 0..line.len()),
+        None => return (0..runs.len()).collect(),
+    };
+    let mut start = 0;
+    let mut arranged: Vec<(u8, usize)> = runs.iter().enumerate()
+        .map(|(idx, run)| {
+            let level_at = |(offset, _): (usize, char)| levels[start + offset].number();
+            let level = run.char_indices().filter(|&(_, ch)| !ch.is_whitespace()).map(level_at).min()
+                .or_else(|| run.char_indices().map(level_at).min());
+            start += run.len();
+            (level.unwrap_or(0), idx)
+        })
+        .collect();
+
+    // Rule L2: from the highest level down to the lowest odd one, reverse every sequence
+    // of runs at that level or higher
+    let highest = arranged.iter().map(|&(level, _)| level).max().unwrap_or(0);
+    let lowest_odd = arranged.iter().map(|&(level, _)| level).min().unwrap_or(0) | 1;
+    for level in (lowest_odd..=highest).rev() {
+        let mut start = 0;
+        while start < arranged.len() {
+            if arranged[start].0 < level {
+                start += 1;
+                continue;
+            }
+            let end = arranged[start..].iter()
+                .position(|&(run_level, _)| run_level < level)
+                .map_or(arranged.len(), |len| start + len);
+            arranged[start..end].reverse();
+            start = end;
+        }
+    }
+    arranged.into_iter().map(|(_, idx)| idx).collect()
+}
+
+#[cfg(feature = "shaping")]
+fn is_rtl(ch: char) -> bool {
+    matches!(ch as u32, 0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF)
+}
+
+#[cfg(feature = "shaping")]
+mod arabic {
+    use std::char;
+    use std::ops::Range;
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Joining {
+        /// Joins only to the preceding letter
+        Right,
+        /// Joins to both neighbours
+        Dual,
+        /// Joins to both neighbours but has no presentation forms (tatweel)
+        Causing,
+        NonJoining,
+    }
+
+    /// Joining type and isolated presentation form of a letter. The final, initial and medial
+    /// forms follow the isolated one in the Arabic Presentation Forms-B block, or in the -A
+    /// block for the Persian and Urdu letters of the Arabic block past U+064A.
+    fn letter(ch: char) -> Option<(Joining, u32)> {
+        use self::Joining::*;
+
+        let letter = match ch as u32 {
+            0x0621 => (NonJoining, 0xFE80),
+            0x0622 => (Right, 0xFE81),
+            0x0623 => (Right, 0xFE83),
+            0x0624 => (Right, 0xFE85),
+            0x0625 => (Right, 0xFE87),
+            0x0626 => (Dual, 0xFE89),
+            0x0627 => (Right, 0xFE8D),
+            0x0628 => (Dual, 0xFE8F),
+            0x0629 => (Right, 0xFE93),
+            0x062A => (Dual, 0xFE95),
+            0x062B => (Dual, 0xFE99),
+            0x062C => (Dual, 0xFE9D),
+            0x062D => (Dual, 0xFEA1),
+            0x062E => (Dual, 0xFEA5),
+            0x062F => (Right, 0xFEA9),
+            0x0630 => (Right, 0xFEAB),
+            0x0631 => (Right, 0xFEAD),
+            0x0632 => (Right, 0xFEAF),
+            0x0633 => (Dual, 0xFEB1),
+            0x0634 => (Dual, 0xFEB5),
+            0x0635 => (Dual, 0xFEB9),
+            0x0636 => (Dual, 0xFEBD),
+            0x0637 => (Dual, 0xFEC1),
+            0x0638 => (Dual, 0xFEC5),
+            0x0639 => (Dual, 0xFEC9),
+            0x063A => (Dual, 0xFECD),
+            0x0640 => (Causing, 0x0640),
+            0x0641 => (Dual, 0xFED1),
+            0x0642 => (Dual, 0xFED5),
+            0x0643 => (Dual, 0xFED9),
+            0x0644 => (Dual, 0xFEDD),
+            0x0645 => (Dual, 0xFEE1),
+            0x0646 => (Dual, 0xFEE5),
+            0x0647 => (Dual, 0xFEE9),
+            0x0648 => (Right, 0xFEED),
+            0x0649 => (Right, 0xFEEF),
+            0x064A => (Dual, 0xFEF1),
+            0x0679 => (Dual, 0xFB66),
+            0x067E => (Dual, 0xFB56),
+            0x0686 => (Dual, 0xFB7A),
+            0x0688 => (Right, 0xFB88),
+            0x0691 => (Right, 0xFB8C),
+            0x0698 => (Right, 0xFB8A),
+            0x06A4 => (Dual, 0xFB6A),
+            0x06A9 => (Dual, 0xFB8E),
+            0x06AF => (Dual, 0xFB92),
+            0x06BE => (Dual, 0xFBAA),
+            0x06C1 => (Dual, 0xFBA6),
+            0x06CC => (Dual, 0xFBFC),
+            0x06D2 => (Right, 0xFBAE),
+            _ => return None,
+        };
+        Some(letter)
+    }
+
+    /// Isolated form of the lam-alef ligature; the final form follows it.
+    fn lam_alef(alef: char) -> Option<u32> {
+        match alef as u32 {
+            0x0622 => Some(0xFEF5),
+            0x0623 => Some(0xFEF7),
+            0x0625 => Some(0xFEF9),
+            0x0627 => Some(0xFEFB),
+            _ => None,
+        }
+    }
+
+    /// Harakat and other marks that do not break joining
+    fn is_transparent(ch: char) -> bool {
+        matches!(ch as u32, 0x0610..=0x061A | 0x064B..=0x065F | 0x0670 | 0x06D6..=0x06DC | 0x06DF..=0x06E4)
+    }
+
+    fn joining(ch: char) -> Joining {
+        letter(ch).map_or(Joining::NonJoining, |(joining, _)| joining)
+    }
+
+    fn joins_previous(chars: &[char], idx: usize) -> bool {
+        chars[..idx].iter().rev()
+            .find(|&&ch| !is_transparent(ch))
+            .map_or(false, |&ch| matches!(joining(ch), Joining::Dual | Joining::Causing))
+    }
+
+    fn joins_next(chars: &[char], idx: usize) -> bool {
+        chars[idx + 1..].iter()
+            .find(|&&ch| !is_transparent(ch))
+            .map_or(false, |&ch| joining(ch) != Joining::NonJoining)
+    }
+
+    fn form(code: u32) -> char {
+        char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
+    /// Joined text and, for every char of it, its byte index and the byte range
+    /// of the chars of `text` it stands for.
+    pub(super) fn join(text: &str) -> (String, Vec<(usize, Range<usize>)>) {
+        let chars: Vec<char> = text.chars().collect();
+        let offsets: Vec<usize> = text.char_indices().map(|(idx, _)| idx).chain(Some(text.len())).collect();
+        let mut joined = String::with_capacity(text.len());
+        let mut sources = Vec::with_capacity(chars.len());
+
+        let mut idx = 0;
+        while idx < chars.len() {
+            let ch = chars[idx];
+            let prev = joins_previous(&chars, idx);
+
+            if ch == '\u{0644}' {
+                if let Some(ligature) = chars.get(idx + 1).and_then(|&next| lam_alef(next)) {
+                    sources.push((joined.len(), offsets[idx]..offsets[idx + 2]));
+                    joined.push(form(if prev { ligature + 1 } else { ligature }));
+                    idx += 2;
+                    continue;
+                }
+            }
+
+            sources.push((joined.len(), offsets[idx]..offsets[idx + 1]));
+
+            match letter(ch) {
+                Some((Joining::Dual, isolated)) => {
+                    let next = joins_next(&chars, idx);
+                    let offset = match (prev, next) {
+                        (false, false) => 0,
+                        (true, false) => 1,
+                        (false, true) => 2,
+                        (true, true) => 3,
+                    };
+                    joined.push(form(isolated + offset));
+                },
+                Some((Joining::Right, isolated)) =>
+                    joined.push(form(if prev { isolated + 1 } else { isolated })),
+                Some((_, isolated)) => joined.push(form(isolated)),
+                None => joined.push(ch),
+            }
+            idx += 1;
+        }
+        (joined, sources)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unshaped_content_keeps_its_indices() {
+        let shaped = shaped("abc");
+        assert_eq!(shaped.content, "abc");
+        assert_eq!(shaped.order.visual_caret(2), 2);
+        assert_eq!(shaped.order.logical_caret(1), 1);
+        assert_eq!(shaped.order.visual_ranges(1, 3), vec![1..3]);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn right_to_left_indices_map_through_the_reorder() {
+        // "ab " then alef and bet, which are drawn as bet, alef
+        let shaped = shaped("ab \u{05D0}\u{05D1}");
+        assert_eq!(shaped.content, "ab \u{05D1}\u{05D0}");
+        let order = &shaped.order;
+
+        assert_eq!(order.visual_caret(1), 1);
+        assert_eq!(order.visual_caret(3), 7);
+        assert_eq!(order.visual_caret(7), 3);
+        assert_eq!(order.visual_ranges(3, 5), vec![5..7]);
+        assert_eq!(order.visual_ranges(1, 5), vec![1..3, 5..7]);
+        assert_eq!(order.logical_caret(5), 5);
+        assert_eq!(order.logical_index(3), 5);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn ligature_covers_both_letters() {
+        let shaped = shaped("\u{0644}\u{0627}");
+        assert_eq!(shaped.content, "\u{FEFB}");
+        let order = &shaped.order;
+
+        assert_eq!(order.logical_index(0), 0);
+        assert_eq!(order.visual_ranges(0, 4), vec![0..3]);
+        assert_eq!(order.visual_caret(2), 3);
+        assert_eq!(order.logical_caret(0), 4);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn persian_letters_take_their_contextual_forms() {
+        // Peh, keheh, farsi yeh: initial, medial and final forms
+        assert_eq!(shaped("\u{067E}\u{06A9}\u{06CC}").content, "\u{FBFD}\u{FB91}\u{FB58}");
+        // Gaf joins the right joining jeh, which does not join the tcheh after it
+        assert_eq!(shaped("\u{06AF}\u{0698}\u{0686}").content, "\u{FB7A}\u{FB8B}\u{FB94}");
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn right_to_left_runs_of_a_line_are_drawn_from_right_to_left() {
+        assert_eq!(visual_order(&["ab ", "cd"]), vec![0, 1]);
+        // Hebrew words after a Latin one keep it on the left
+        assert_eq!(visual_order(&["ab ", "\u{05D0}\u{05D1} ", "\u{05D2}"]), vec![0, 2, 1]);
+        // In a right-to-left line, the embedded Latin words keep their own order
+        assert_eq!(visual_order(&["\u{05D0} ", "ab ", "cd ", "\u{05D1}"]), vec![3, 1, 2, 0]);
+    }
+}
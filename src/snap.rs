@@ -0,0 +1,195 @@
+use exgui::Real;
+use crate::BoundingBox;
+use crate::dpi::DeviceScale;
+use crate::path::{Point, Segment};
+
+/// Rounds the coordinates of untransformed shapes to device pixels. Lines of strokes
+/// with an odd width in device pixels go through pixel centers, other edges lie on pixel
+/// boundaries, so both cover whole pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PixelSnap {
+    ratio: Real,
+}
+
+impl PixelSnap {
+    pub(crate) fn new(scale: DeviceScale) -> Self {
+        PixelSnap {
+            ratio: scale.ratio(),
+        }
+    }
+
+    /// Whole device pixels, at least one for visible strokes.
+    pub(crate) fn stroke_width(&self, width: Real) -> Real {
+        if width > 0.0 {
+            (width * self.ratio).round().max(1.0) / self.ratio
+        } else {
+            width
+        }
+    }
+
+    /// `stroke_width` is the snapped width of the stroke drawn along the coordinate.
+    pub(crate) fn coord(&self, value: Real, stroke_width: Option<Real>) -> Real {
+        let device = value * self.ratio;
+        let odd = stroke_width.map_or(false, |width| (width * self.ratio).round() as i64 % 2 == 1);
+        let snapped = if odd { device.floor() + 0.5 } else { device.round() };
+        snapped / self.ratio
+    }
+
+    pub(crate) fn point(&self, (x, y): Point, stroke_width: Option<Real>) -> Point {
+        (self.coord(x, stroke_width), self.coord(y, stroke_width))
+    }
+
+    pub(crate) fn rect(&self, rect: &BoundingBox, stroke_width: Option<Real>) -> BoundingBox {
+        BoundingBox {
+            min_x: self.coord(rect.min_x, stroke_width),
+            min_y: self.coord(rect.min_y, stroke_width),
+            max_x: self.coord(rect.max_x, stroke_width),
+            max_y: self.coord(rect.max_y, stroke_width),
+        }
+    }
+
+    /// A radius of whole device pixels keeps the edges of a snapped circle on the pixel grid.
+    pub(crate) fn radius(&self, r: Real) -> Real {
+        (r * self.ratio).round() / self.ratio
+    }
+
+    /// Scroll offsets move snapped shapes by whole device pixels.
+    pub(crate) fn offset(&self, offset: (Real, Real)) -> (Real, Real) {
+        self.point(offset, None)
+    }
+
+    pub(crate) fn segments(&self, segments: &[Segment], stroke_width: Option<Real>) -> Vec<Segment> {
+        let point = |point| self.point(point, stroke_width);
+        segments.iter()
+            .map(|segment| match *segment {
+                Segment::Move(to) => Segment::Move(point(to)),
+                Segment::Line(to) => Segment::Line(point(to)),
+                Segment::Quad(ctrl, to) => Segment::Quad(point(ctrl), point(to)),
+                Segment::Cubic(ctrl1, ctrl2, to) => Segment::Cubic(point(ctrl1), point(ctrl2), point(to)),
+                Segment::Close | Segment::Unsupported(_) => *segment,
+            })
+            .collect()
+    }
+}
+
+/// Offset of the children of a node drawn at `offset`, scrolled by `scroll` and moved by its
+/// anchor `shift`. The sum is snapped at once, a fractional shift added after snapping the
+/// scrolled offset would move the children off the grid.
+pub(crate) fn child_offset(snap: Option<PixelSnap>,
+                           offset: (Real, Real),
+                           scroll: (Real, Real),
+                           shift: (Real, Real)) -> (Real, Real)
+{
+    let offset = (offset.0 - scroll.0 + shift.0, offset.1 - scroll.1 + shift.1);
+    snap.map_or(offset, |snap| snap.offset(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATIOS: [Real; 3] = [1.0, 1.5, 2.0];
+
+    fn snaps() -> impl Iterator<Item = (Real, PixelSnap)> {
+        RATIOS.iter().map(|&ratio| (ratio, PixelSnap::new(DeviceScale::new(ratio))))
+    }
+
+    fn is_whole(device: Real) -> bool {
+        (device - device.round()).abs() < 1e-4
+    }
+
+    fn is_center(device: Real) -> bool {
+        (device - device.floor() - 0.5).abs() < 1e-4
+    }
+
+    #[test]
+    fn stroke_widths_are_whole_device_pixels() {
+        for (ratio, snap) in snaps() {
+            assert_eq!(snap.stroke_width(0.0), 0.0);
+            assert_eq!(snap.stroke_width(0.1), 1.0 / ratio, "A visible stroke is at least a device pixel");
+            for &width in [1.0, 1.3, 2.0, 2.7].iter() {
+                let snapped = snap.stroke_width(width);
+                assert!(is_whole(snapped * ratio), "{} at {}: {}", width, ratio, snapped);
+                assert!((snapped - width).abs() <= 0.5 / ratio + 1e-4, "{} at {}: {}", width, ratio, snapped);
+            }
+        }
+    }
+
+    #[test]
+    fn coords_lie_on_boundaries_or_centers_by_the_stroke_width() {
+        for (ratio, snap) in snaps() {
+            let (odd, even) = (Some(1.0 / ratio), Some(2.0 / ratio));
+            for &value in [0.0, 10.3, 10.5, 33.74, -7.2].iter() {
+                for &stroke_width in [None, even].iter() {
+                    let snapped = snap.coord(value, stroke_width);
+                    assert!(is_whole(snapped * ratio), "{} at {}: {}", value, ratio, snapped);
+                    assert!((snapped - value).abs() <= 0.5 / ratio + 1e-4, "{} at {}: {}", value, ratio, snapped);
+                }
+                let snapped = snap.coord(value, odd);
+                assert!(is_center(snapped * ratio), "{} at {}: {}", value, ratio, snapped);
+                assert!((snapped - value).abs() <= 1.0 / ratio, "{} at {}: {}", value, ratio, snapped);
+            }
+        }
+    }
+
+    #[test]
+    fn rects_and_radii_snap_to_the_grid() {
+        let rect = BoundingBox { min_x: 10.3, min_y: 20.6, max_x: 110.45, max_y: 70.2 };
+        for (ratio, snap) in snaps() {
+            let snapped = snap.rect(&rect, None);
+            for &edge in [snapped.min_x, snapped.min_y, snapped.max_x, snapped.max_y].iter() {
+                assert!(is_whole(edge * ratio), "{} at {}", edge, ratio);
+            }
+            assert_eq!(snapped.min_x, snap.coord(rect.min_x, None));
+            assert_eq!(snapped.max_y, snap.coord(rect.max_y, None));
+
+            let stroked = snap.rect(&rect, Some(1.0 / ratio));
+            for &edge in [stroked.min_x, stroked.min_y, stroked.max_x, stroked.max_y].iter() {
+                assert!(is_center(edge * ratio), "{} at {}", edge, ratio);
+            }
+
+            let r = snap.radius(10.2);
+            assert!(is_whole(r * ratio), "{} at {}", r, ratio);
+            assert!((r - 10.2).abs() <= 0.5 / ratio + 1e-4);
+        }
+    }
+
+    #[test]
+    fn segments_snap_every_point() {
+        let segments = [
+            Segment::Move((0.3, 0.6)),
+            Segment::Line((10.2, 5.7)),
+            Segment::Quad((3.3, 4.4), (5.5, 6.6)),
+            Segment::Cubic((1.1, 2.2), (3.3, 4.4), (7.7, 8.8)),
+            Segment::Close,
+            Segment::Unsupported(5),
+        ];
+        for (_, snap) in snaps() {
+            let point = |point| snap.point(point, None);
+            assert_eq!(snap.segments(&segments, None), vec![
+                Segment::Move(point((0.3, 0.6))),
+                Segment::Line(point((10.2, 5.7))),
+                Segment::Quad(point((3.3, 4.4)), point((5.5, 6.6))),
+                Segment::Cubic(point((1.1, 2.2)), point((3.3, 4.4)), point((7.7, 8.8))),
+                Segment::Close,
+                Segment::Unsupported(5),
+            ]);
+        }
+    }
+
+    /// A 50.3 by 30.3 rect anchored at the center of a 400 by 300 viewport is shifted by
+    /// (174.85, 134.85), so its scrolled children only stay on the grid if the shift is snapped.
+    #[test]
+    fn children_of_anchored_nodes_snap_after_the_shift() {
+        let (offset, scroll, shift) = ((10.0, 20.0), (0.0, 3.3), (174.85, 134.85));
+        for (ratio, snap) in snaps() {
+            let (x, y) = child_offset(Some(snap), offset, scroll, shift);
+            assert!(is_whole(x * ratio) && is_whole(y * ratio), "({}, {}) at {}", x, y, ratio);
+            assert!((x - 184.85).abs() <= 0.5 / ratio + 1e-4, "{} at {}", x, ratio);
+            assert!((y - 151.55).abs() <= 0.5 / ratio + 1e-4, "{} at {}", y, ratio);
+        }
+
+        let (x, y) = child_offset(None, offset, scroll, shift);
+        assert!((x - 184.85).abs() < 1e-4 && (y - 151.55).abs() < 1e-4, "({}, {})", x, y);
+    }
+}
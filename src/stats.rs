@@ -0,0 +1,44 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Counters of a rendered frame. `nodes` counts the drawn nodes, `recalculated_nodes`
+/// and `cached_nodes` split the nodes of the recalc pass by whether their bound was reused.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderStats {
+    pub nodes: usize,
+    pub recalculated_nodes: usize,
+    pub cached_nodes: usize,
+    pub paths: usize,
+    pub fills: usize,
+    pub strokes: usize,
+    pub text_runs: usize,
+    pub text_measurements: usize,
+    pub recalc_time: Duration,
+    pub draw_time: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderPass {
+    Recalc,
+    Draw,
+}
+
+/// Receives the start and the end of each subtree of the recalc and draw passes.
+pub trait Profiler {
+    fn begin(&mut self, pass: RenderPass, path: &[usize]);
+    fn end(&mut self, pass: RenderPass, path: &[usize]);
+}
+
+pub(crate) struct ProfilerHook(pub Box<dyn Profiler>);
+
+impl ProfilerHook {
+    pub(crate) fn profiler(&mut self) -> &mut dyn Profiler {
+        &mut *self.0
+    }
+}
+
+impl fmt::Debug for ProfilerHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProfilerHook")
+    }
+}
@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+use exgui::{Real, Color, AlignHor, AlignVer};
+use crate::{BoundingBox, NodePath};
+
+/// `Numeric` takes a CSS weight from 100 to 900.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontWeight {
+    Normal,
+    Bold,
+    Numeric(u16),
+}
+
+impl FontWeight {
+    pub fn value(&self) -> u16 {
+        match *self {
+            FontWeight::Normal => 400,
+            FontWeight::Bold => 700,
+            FontWeight::Numeric(weight) => weight,
+        }
+    }
+}
+
+impl Default for FontWeight {
+    fn default() -> Self {
+        FontWeight::Normal
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+}
+
+impl Default for FontStyle {
+    fn default() -> Self {
+        FontStyle::Normal
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextDecoration {
+    pub underline: bool,
+    pub overline: bool,
+    pub line_through: bool,
+}
+
+impl TextDecoration {
+    pub fn is_none(&self) -> bool {
+        !(self.underline || self.overline || self.line_through)
+    }
+}
+
+/// Overrides of the `Text` settings. A span of a `Text` node applies to all its words,
+/// a span of a `Word` overrides it for that word.
+/// `font_name` may name a font family registered with `NanovgRenderer::load_font_face`,
+/// `line_height` is a multiplier of the font line height.
+#[derive(Debug, Clone, Default)]
+pub struct Span {
+    pub font_name: Option<String>,
+    pub font_size: Option<Real>,
+    pub color: Option<Color>,
+    pub weight: Option<FontWeight>,
+    pub style: Option<FontStyle>,
+    pub decoration: Option<TextDecoration>,
+    pub letter_spacing: Option<Real>,
+    pub line_height: Option<Real>,
+}
+
+/// Lays out the words of a `Text` one after another on a shared baseline.
+/// Lines are wrapped at `max_width` or at the width of the parent bound.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextFlow {
+    pub max_width: Option<Real>,
+}
+
+/// Shortening of a `Text` that does not fit the width of its parent bound.
+/// `Clip` drops the glyphs that do not fit, the ellipsis modes replace them with `…`.
+/// It has no effect on a `Text` with `TextFlow`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextOverflow {
+    Clip,
+    Ellipsis,
+    EllipsisMiddle,
+}
+
+/// Caret and selection of an editable `Word`, as byte indices into its logical content;
+/// with the `shaping` feature they are mapped through the bidi reorder, so a selection
+/// of mixed-direction text may be drawn in several pieces. They follow the word as laid out:
+/// a selection gets a rect on every line of a flowed word and content shortened by
+/// a `TextOverflow` is selected with its ellipsis.
+/// Colors default to the text color for the caret and a translucent blue for the selection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextSelection {
+    pub caret: Option<usize>,
+    pub range: Option<(usize, usize)>,
+    pub caret_width: Real,
+    pub caret_color: Option<Color>,
+    pub selection_color: Option<Color>,
+}
+
+impl Default for TextSelection {
+    fn default() -> Self {
+        TextSelection {
+            caret: None,
+            range: None,
+            caret_width: 1.0,
+            caret_color: None,
+            selection_color: None,
+        }
+    }
+}
+
+/// An absolute size or a percentage of the parent bound size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Px(Real),
+    Pct(Real),
+}
+
+impl Length {
+    pub fn resolve(&self, parent_size: Real) -> Real {
+        match *self {
+            Length::Px(px) => px,
+            Length::Pct(pct) => parent_size * pct / 100.0,
+        }
+    }
+}
+
+/// Limits of the percentage or auto sized width and height of a `Rect` or diameter of a `Circle`,
+/// applied after both the percentage and the auto sizing. A min limit wins over a max one.
+/// `aspect_ratio` is width to height; the height follows the width unless it hits its limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SizeConstraints {
+    pub min_width: Option<Length>,
+    pub max_width: Option<Length>,
+    pub min_height: Option<Length>,
+    pub max_height: Option<Length>,
+    pub aspect_ratio: Option<Real>,
+}
+
+impl SizeConstraints {
+    /// Constrains the dimensions resolved by percentage or auto sizing, `free` tells which
+    /// of them were; fixed dimensions are kept and drive the aspect ratio.
+    pub(crate) fn apply(&self,
+                        (width, height): (Real, Real),
+                        (parent_width, parent_height): (Real, Real),
+                        (width_free, height_free): (bool, bool)) -> (Real, Real)
+    {
+        let clamp = |size: Real, min: Option<Length>, max: Option<Length>, parent_size: Real| {
+            let size = max.map_or(size, |max| size.min(max.resolve(parent_size)));
+            min.map_or(size, |min| size.max(min.resolve(parent_size)))
+        };
+        let clamp_width = |width| clamp(width, self.min_width, self.max_width, parent_width);
+        let clamp_height = |height| clamp(height, self.min_height, self.max_height, parent_height);
+
+        let width = if width_free { clamp_width(width) } else { width };
+        let height = if height_free { clamp_height(height) } else { height };
+        match self.aspect_ratio {
+            Some(ratio) if ratio > 0.0 => match (width_free, height_free) {
+                (_, true) => {
+                    let height = clamp_height(width / ratio);
+                    let width = if width_free { clamp_width(height * ratio) } else { width };
+                    (width, height)
+                },
+                (true, false) => (clamp_width(height * ratio), height),
+                (false, false) => (width, height),
+            },
+            _ => (width, height),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnchorPoint {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// Places a node at a point of its parent bound instead of at its own `x`/`y` (`cx`/`cy`
+/// of a circle, the aligned position of a text). `offset` moves it inwards from the edges
+/// it is pinned to, and right and down from the centers.
+///
+/// Like a percentage, the anchor of a rect or circle resolves into the shape's own
+/// position; the children of an auto sized one are drawn moved along with it. A text keeps
+/// its position and alignment, the anchored ones only place its words.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Anchor {
+    pub point: AnchorPoint,
+    pub offset: (Length, Length),
+}
+
+impl Anchor {
+    pub fn new(point: AnchorPoint) -> Self {
+        Anchor {
+            point,
+            offset: (Length::Px(0.0), Length::Px(0.0)),
+        }
+    }
+
+    pub fn with_offset(mut self, x: Length, y: Length) -> Self {
+        self.offset = (x, y);
+        self
+    }
+
+    fn factors(&self) -> (Real, Real) {
+        match self.point {
+            AnchorPoint::TopLeft => (0.0, 0.0),
+            AnchorPoint::Top => (0.5, 0.0),
+            AnchorPoint::TopRight => (1.0, 0.0),
+            AnchorPoint::Left => (0.0, 0.5),
+            AnchorPoint::Center => (0.5, 0.5),
+            AnchorPoint::Right => (1.0, 0.5),
+            AnchorPoint::BottomLeft => (0.0, 1.0),
+            AnchorPoint::Bottom => (0.5, 1.0),
+            AnchorPoint::BottomRight => (1.0, 1.0),
+        }
+    }
+
+    /// Top left corner of a box of `size` anchored in `parent_bound`.
+    pub(crate) fn position(&self, (width, height): (Real, Real), parent_bound: &BoundingBox) -> (Real, Real) {
+        let (factor_x, factor_y) = self.factors();
+        let place = |factor: Real, min: Real, parent_size: Real, size: Real, offset: Length| {
+            let offset = offset.resolve(parent_size);
+            min + (parent_size - size) * factor + if factor == 1.0 { -offset } else { offset }
+        };
+
+        (
+            place(factor_x, parent_bound.min_x, parent_bound.width(), width, self.offset.0),
+            place(factor_y, parent_bound.min_y, parent_bound.height(), height, self.offset.1),
+        )
+    }
+
+    /// Text alignment that puts the text box on the anchor side of its position.
+    pub(crate) fn text_align(&self) -> (AlignHor, AlignVer) {
+        let (factor_x, factor_y) = self.factors();
+        let hor = if factor_x == 0.0 {
+            AlignHor::Left
+        } else if factor_x == 1.0 {
+            AlignHor::Right
+        } else {
+            AlignHor::Center
+        };
+        let ver = if factor_y == 0.0 {
+            AlignVer::Top
+        } else if factor_y == 1.0 {
+            AlignVer::Bottom
+        } else {
+            AlignVer::Middle
+        };
+        (hor, ver)
+    }
+}
+
+/// Coordinate space of the commands of a `Path`, mapped onto the parent bound.
+/// `Pct` is a `0..100` space on both axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathUnits {
+    Pct,
+    ViewBox(ViewBox),
+}
+
+/// A view box as in SVG. With `preserve_aspect_ratio` it is scaled uniformly to fit
+/// the parent bound and centered in it, otherwise it is stretched to fill it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewBox {
+    pub min_x: Real,
+    pub min_y: Real,
+    pub width: Real,
+    pub height: Real,
+    pub preserve_aspect_ratio: bool,
+}
+
+impl ViewBox {
+    pub fn new(min_x: Real, min_y: Real, width: Real, height: Real) -> Self {
+        ViewBox {
+            min_x,
+            min_y,
+            width,
+            height,
+            preserve_aspect_ratio: true,
+        }
+    }
+
+    /// The `0..1` space on both axes, stretched to the parent bound.
+    pub fn normalized() -> Self {
+        ViewBox::new(0.0, 0.0, 1.0, 1.0).stretched()
+    }
+
+    pub fn stretched(mut self) -> Self {
+        self.preserve_aspect_ratio = false;
+        self
+    }
+}
+
+impl PathUnits {
+    /// Matrix mapping path coordinates onto `parent_bound`.
+    pub(crate) fn matrix(&self, parent_bound: &BoundingBox) -> [Real; 6] {
+        let view_box = match *self {
+            PathUnits::Pct => ViewBox::new(0.0, 0.0, 100.0, 100.0).stretched(),
+            PathUnits::ViewBox(view_box) => view_box,
+        };
+        let scale = |size: Real, view_size: Real| if view_size != 0.0 { size / view_size } else { 0.0 };
+        let mut scale_x = scale(parent_bound.width(), view_box.width);
+        let mut scale_y = scale(parent_bound.height(), view_box.height);
+        let (mut offset_x, mut offset_y) = (0.0, 0.0);
+        if view_box.preserve_aspect_ratio {
+            let uniform = scale_x.min(scale_y);
+            offset_x = (parent_bound.width() - view_box.width * uniform) / 2.0;
+            offset_y = (parent_bound.height() - view_box.height * uniform) / 2.0;
+            scale_x = uniform;
+            scale_y = uniform;
+        }
+
+        [
+            scale_x, 0.0,
+            0.0, scale_y,
+            parent_bound.min_x + offset_x - view_box.min_x * scale_x,
+            parent_bound.min_y + offset_y - view_box.min_y * scale_y,
+        ]
+    }
+}
+
+/// Makes a `Rect` a viewport: its children are clipped to it and moved up and left by `offset`,
+/// which is clamped to the extent of their bounds. The offset doesn't change the layout,
+/// so `NanovgRenderer::scroll_to` updates it without a recalc.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Scroll {
+    pub offset: (Real, Real),
+    pub scrollbars: Option<Scrollbars>,
+}
+
+/// Thumbs drawn inside the right and bottom edges of a scrolled viewport, for the axes
+/// whose content overflows. A thumb is as long as the visible fraction of the track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scrollbars {
+    pub width: Real,
+    pub min_thumb_length: Real,
+    pub color: Option<Color>,
+}
+
+impl Default for Scrollbars {
+    fn default() -> Self {
+        Scrollbars {
+            width: 6.0,
+            min_thumb_length: 16.0,
+            color: None,
+        }
+    }
+}
+
+/// `Hidden` subtrees keep their place in the layout but are not drawn, `Collapsed` ones,
+/// like `display: none`, are neither laid out nor drawn and don't size their parent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    Visible,
+    Hidden,
+    Collapsed,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::Visible
+    }
+}
+
+/// Renderer-side properties of a node, addressed by its `NodePath`.
+/// Siblings are drawn by ascending `z_index`; an `overlay` subtree is drawn after the whole
+/// tree, above other nodes and outside of scroll viewports. `opacity` multiplies the alpha
+/// of the node and of its subtree. Unset properties take their defaults: z-index 0,
+/// no overlay, visible and opaque.
+///
+/// A style belongs to a position in the tree, not to a node: it follows a node only as long
+/// as the node's path is stable. Inserting or removing an earlier sibling of the node or of
+/// one of its ancestors shifts the path, and the style then applies to whatever node takes
+/// that position. Keep conditional children in their slots, e.g. collapse them with
+/// `Visibility::Collapsed` instead of leaving them out, or set the styles again after
+/// the shape of the tree changes.
+#[derive(Debug, Clone, Default)]
+pub struct NodeStyle {
+    pub flow: Option<TextFlow>,
+    pub span: Option<Span>,
+    pub overflow: Option<TextOverflow>,
+    pub selection: Option<TextSelection>,
+    pub constraints: Option<SizeConstraints>,
+    pub anchor: Option<Anchor>,
+    pub path_units: Option<PathUnits>,
+    pub scroll: Option<Scroll>,
+    pub z_index: Option<i32>,
+    pub overlay: Option<bool>,
+    pub visibility: Option<Visibility>,
+    pub opacity: Option<Real>,
+}
+
+impl NodeStyle {
+    /// The properties set in `other` replace those of `self`, unset ones keep their values.
+    pub fn merge(&mut self, other: NodeStyle) {
+        self.flow = other.flow.or(self.flow.take());
+        self.span = other.span.or(self.span.take());
+        self.overflow = other.overflow.or(self.overflow.take());
+        self.selection = other.selection.or(self.selection.take());
+        self.constraints = other.constraints.or(self.constraints.take());
+        self.anchor = other.anchor.or(self.anchor.take());
+        self.path_units = other.path_units.or(self.path_units.take());
+        self.scroll = other.scroll.or(self.scroll.take());
+        self.z_index = other.z_index.or(self.z_index);
+        self.overlay = other.overlay.or(self.overlay);
+        self.visibility = other.visibility.or(self.visibility);
+        self.opacity = other.opacity.or(self.opacity);
+    }
+
+    /// Whether the properties the recalc pass reads are the same. The others, such as
+    /// the z-index, opacity, selection, span color and scroll offset, are only drawn.
+    pub(crate) fn same_layout(&self, other: &NodeStyle) -> bool {
+        let span = |style: &NodeStyle| style.span.as_ref().map(|span| (
+            span.font_name.clone(),
+            span.font_size,
+            span.weight,
+            span.style,
+            span.decoration,
+            span.letter_spacing,
+            span.line_height,
+        ));
+        let collapsed = |style: &NodeStyle| style.visibility == Some(Visibility::Collapsed);
+
+        self.flow == other.flow
+            && span(self) == span(other)
+            && self.overflow == other.overflow
+            && self.constraints == other.constraints
+            && self.anchor == other.anchor
+            && self.path_units == other.path_units
+            && self.scroll.is_some() == other.scroll.is_some()
+            && collapsed(self) == collapsed(other)
+    }
+}
+
+pub(crate) fn visibility(styles: &HashMap<NodePath, NodeStyle>, path: &[usize]) -> Visibility {
+    styles.get(path).and_then(|style| style.visibility).unwrap_or_default()
+}
+
+/// Spans of a `Word` at `path`: the span of its `Text` followed by its own span.
+pub(crate) fn word_spans<'a>(styles: &'a HashMap<NodePath, NodeStyle>, path: &[usize]) -> Vec<&'a Span> {
+    let mut spans = Vec::new();
+    if let Some((_, text_path)) = path.split_last() {
+        spans.extend(styles.get(text_path).and_then(|style| style.span.as_ref()));
+    }
+    spans.extend(styles.get(path).and_then(|style| style.span.as_ref()));
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drawn_properties_keep_the_layout() {
+        let style = NodeStyle {
+            anchor: Some(Anchor::new(AnchorPoint::Center)),
+            ..Default::default()
+        };
+        let mut drawn = style.clone();
+        drawn.merge(NodeStyle {
+            z_index: Some(3),
+            opacity: Some(0.5),
+            scroll: Some(Scroll::default()),
+            ..Default::default()
+        });
+        assert!(!style.same_layout(&drawn), "Adding a scroll adds a scroll extent");
+
+        let mut scrolled = drawn.clone();
+        scrolled.merge(NodeStyle {
+            scroll: Some(Scroll { offset: (0.0, 20.0), ..Scroll::default() }),
+            visibility: Some(Visibility::Hidden),
+            ..Default::default()
+        });
+        assert!(drawn.same_layout(&scrolled));
+
+        scrolled.merge(NodeStyle { visibility: Some(Visibility::Collapsed), ..Default::default() });
+        assert!(!drawn.same_layout(&scrolled));
+    }
+
+    #[test]
+    fn merge_keeps_the_unset_properties() {
+        let mut style = NodeStyle {
+            flow: Some(TextFlow { max_width: Some(100.0) }),
+            z_index: Some(2),
+            visibility: Some(Visibility::Hidden),
+            ..Default::default()
+        };
+        style.merge(NodeStyle {
+            z_index: Some(-1),
+            opacity: Some(0.5),
+            ..Default::default()
+        });
+
+        assert_eq!(style.flow.and_then(|flow| flow.max_width), Some(100.0));
+        assert_eq!(style.z_index, Some(-1));
+        assert_eq!(style.visibility, Some(Visibility::Hidden));
+        assert_eq!(style.opacity, Some(0.5));
+        assert!(style.anchor.is_none());
+    }
+
+    #[test]
+    fn unset_visibility_is_visible() {
+        let mut styles = HashMap::new();
+        styles.insert(vec![0], NodeStyle { opacity: Some(0.5), ..Default::default() });
+        styles.insert(vec![1], NodeStyle { visibility: Some(Visibility::Collapsed), ..Default::default() });
+
+        assert_eq!(visibility(&styles, &[0]), Visibility::Visible);
+        assert_eq!(visibility(&styles, &[1]), Visibility::Collapsed);
+        assert_eq!(visibility(&styles, &[2]), Visibility::Visible);
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use crate::style::{FontWeight, FontStyle};
+use crate::ttf::{read_u16, font_offset, table_range};
+
+const FONT_EXTENSIONS: [&str; 3] = ["ttf", "otf", "ttc"];
+
+const NAME_FAMILY: u16 = 1;
+const NAME_SUBFAMILY: u16 = 2;
+const NAME_TYPOGRAPHIC_FAMILY: u16 = 16;
+const NAME_TYPOGRAPHIC_SUBFAMILY: u16 = 17;
+
+const FS_SELECTION_ITALIC: u16 = 1;
+const FS_SELECTION_OBLIQUE: u16 = 1 << 9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemFace {
+    pub family: String,
+    pub subfamily: String,
+    pub path: PathBuf,
+    pub weight: FontWeight,
+    pub style: FontStyle,
+}
+
+impl SystemFace {
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.family, self.subfamily)
+    }
+}
+
+pub(crate) enum SystemMatch {
+    Family(Vec<SystemFace>),
+    Face(SystemFace),
+}
+
+/// Index of the fonts installed in the standard font directories and in added ones.
+/// Directories are scanned on the first lookup.
+#[derive(Debug, Clone)]
+pub struct SystemFonts {
+    dirs: Vec<PathBuf>,
+    faces: Option<Vec<SystemFace>>,
+    missing: HashSet<String>,
+}
+
+impl Default for SystemFonts {
+    fn default() -> Self {
+        let mut dirs = vec![
+            PathBuf::from("/usr/share/fonts"),
+            PathBuf::from("/usr/local/share/fonts"),
+        ];
+        if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+            dirs.push(home.join(".fonts"));
+            dirs.push(home.join(".local/share/fonts"));
+        }
+
+        SystemFonts {
+            dirs,
+            faces: None,
+            missing: HashSet::new(),
+        }
+    }
+}
+
+impl SystemFonts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.dirs.push(dir.into());
+        self.faces = None;
+        self.missing.clear();
+        self
+    }
+
+    pub fn faces(&mut self) -> &[SystemFace] {
+        if self.faces.is_none() {
+            let mut faces = Vec::new();
+            let mut visited = HashSet::new();
+            for dir in self.dirs.iter() {
+                scan(dir, &mut visited, &mut faces);
+            }
+            self.faces = Some(faces);
+        }
+        self.faces.as_ref().map_or(&[], |faces| faces.as_slice())
+    }
+
+    /// Looks `name` up as a family name first and as a full face name second,
+    /// ignoring case. Names that matched nothing are not looked up again.
+    pub(crate) fn find(&mut self, name: &str) -> Option<SystemMatch> {
+        if self.missing.contains(name) {
+            return None;
+        }
+
+        let family: Vec<_> = self.faces().iter()
+            .filter(|face| face.family.eq_ignore_ascii_case(name))
+            .cloned()
+            .collect();
+        let found = if !family.is_empty() {
+            Some(SystemMatch::Family(family))
+        } else {
+            self.faces().iter()
+                .find(|face| face.full_name().eq_ignore_ascii_case(name))
+                .cloned()
+                .map(SystemMatch::Face)
+        };
+
+        if found.is_none() {
+            self.missing.insert(name.to_string());
+        }
+        found
+    }
+}
+
+/// Follows symlinked directories, but scans every directory once, so links to
+/// a parent directory do not make it loop.
+fn scan(dir: &Path, visited: &mut HashSet<PathBuf>, faces: &mut Vec<SystemFace>) {
+    match fs::canonicalize(dir) {
+        Ok(canonical) if visited.insert(canonical) => (),
+        _ => return,
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        if path.is_dir() {
+            scan(&path, visited, faces);
+        } else if is_font_file(&path) {
+            if let Some(face) = read_face(&path) {
+                faces.push(face);
+            }
+        }
+    }
+}
+
+fn is_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| FONT_EXTENSIONS.iter().any(|font_ext| font_ext.eq_ignore_ascii_case(ext)))
+}
+
+/// Reads the names, weight and style of the first face of a TrueType or OpenType file.
+/// Only the table directory and the `name` and `OS/2` tables are read from the file.
+fn read_face(path: &Path) -> Option<SystemFace> {
+    let mut file = File::open(path).ok()?;
+    let file_len = file.metadata().ok()?.len();
+    let mut read = |offset: usize, len: usize| -> Option<Vec<u8>> {
+        if offset as u64 + len as u64 > file_len {
+            return None;
+        }
+        let mut data = vec![0; len];
+        file.seek(SeekFrom::Start(offset as u64)).ok()?;
+        file.read_exact(&mut data).ok()?;
+        Some(data)
+    };
+
+    let font_offset = font_offset(&read(0, 16)?)?;
+    let num_tables = read_u16(&read(font_offset, 12)?, 4)? as usize;
+    let directory = read(font_offset, 12 + num_tables * 16)?;
+
+    let name = table_range(&directory, 0, b"name").and_then(|(offset, len)| read(offset, len))?;
+    let family = name_string(&name, NAME_TYPOGRAPHIC_FAMILY)
+        .or_else(|| name_string(&name, NAME_FAMILY))?;
+    let subfamily = name_string(&name, NAME_TYPOGRAPHIC_SUBFAMILY)
+        .or_else(|| name_string(&name, NAME_SUBFAMILY))
+        .unwrap_or_else(|| "Regular".to_string());
+
+    let os2 = table_range(&directory, 0, b"OS/2").and_then(|(offset, len)| read(offset, len));
+    let (weight, style) = match os2 {
+        Some(os2) => {
+            let weight = read_u16(&os2, 4).unwrap_or(400);
+            let selection = read_u16(&os2, 62).unwrap_or(0);
+            let style = if selection & (FS_SELECTION_ITALIC | FS_SELECTION_OBLIQUE) != 0 {
+                FontStyle::Italic
+            } else {
+                FontStyle::Normal
+            };
+            (weight, style)
+        },
+        None => (400, FontStyle::Normal),
+    };
+
+    Some(SystemFace {
+        family,
+        subfamily,
+        path: path.to_path_buf(),
+        weight: FontWeight::Numeric(weight),
+        style,
+    })
+}
+
+/// Name `name_id` from the `name` table `data`. Windows and Unicode platform names
+/// are UTF-16BE, Macintosh ones are read as Latin-1.
+fn name_string(data: &[u8], name_id: u16) -> Option<String> {
+    let count = read_u16(data, 2)? as usize;
+    let strings = read_u16(data, 4)? as usize;
+
+    let mut fallback = None;
+    for record in (0..count).map(|idx| 6 + idx * 12) {
+        if read_u16(data, record + 6)? != name_id {
+            continue;
+        }
+        let platform = read_u16(data, record)?;
+        let length = read_u16(data, record + 8)? as usize;
+        let offset = strings + read_u16(data, record + 10)? as usize;
+        let bytes = data.get(offset..offset + length)?;
+
+        match platform {
+            0 | 3 => {
+                let units: Vec<u16> = bytes.chunks(2)
+                    .filter(|unit| unit.len() == 2)
+                    .map(|unit| u16::from(unit[0]) << 8 | u16::from(unit[1]))
+                    .collect();
+                return String::from_utf16(&units).ok();
+            },
+            1 => fallback = Some(bytes.iter().map(|&byte| char::from(byte)).collect()),
+            _ => (),
+        }
+    }
+    fallback
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ROBOTO;
+
+    #[test]
+    fn reads_the_face_from_its_tables() {
+        let face = read_face(Path::new(ROBOTO)).expect("Roboto must be readable");
+        assert_eq!(face.family, "Roboto");
+        assert_eq!(face.subfamily, "Regular");
+        assert_eq!(face.weight, FontWeight::Numeric(400));
+        assert_eq!(face.style, FontStyle::Normal);
+    }
+
+    #[test]
+    fn added_dirs_are_searched_for_names_missing_before() {
+        let mut system_fonts = SystemFonts { dirs: Vec::new(), faces: None, missing: HashSet::new() };
+        assert!(system_fonts.find("Roboto").is_none());
+
+        let mut system_fonts = system_fonts.with_dir("resources");
+        assert!(matches!(system_fonts.find("Roboto"), Some(SystemMatch::Family(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_survives_a_symlink_cycle() {
+        let dir = env::temp_dir().join(format!("exgui-system-fonts-{}", std::process::id()));
+        let fonts = dir.join("fonts");
+        fs::create_dir_all(&fonts).expect("Temp dir must be writable");
+        fs::copy(ROBOTO, fonts.join("Roboto-Regular.ttf")).expect("Roboto must be copyable");
+        let _ = std::os::unix::fs::symlink(&dir, fonts.join("loop"));
+
+        let mut system_fonts = SystemFonts { dirs: vec![dir.clone()], faces: None, missing: HashSet::new() };
+        let count = system_fonts.faces().len();
+        fs::remove_dir_all(&dir).expect("Temp dir must be removable");
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use exgui::{Real, Text, Color, AlignHor, AlignVer};
+use crate::{BoundingBox, NodePath};
+use crate::measure::{Measure, TextMeasurer, FontMetrics, GlyphPosition};
+use crate::font::FontFamilies;
+use crate::shaping;
+use crate::style::{Span, FontWeight, FontStyle, TextDecoration, TextOverflow};
+
+const EMBOLDEN_RATIO: Real = 1.0 / 24.0;
+pub(crate) const OBLIQUE_SKEW: Real = 0.2;
+const DECORATION_THICKNESS_RATIO: Real = 1.0 / 18.0;
+const UNDERLINE_OFFSET_RATIO: Real = 0.4;
+const LINE_THROUGH_OFFSET_RATIO: Real = 0.3;
+pub(crate) const ELLIPSIS: &str = "\u{2026}";
+
+pub(crate) type TextLayouts = HashMap<NodePath, Vec<TextRun>>;
+
+/// A piece of a word placed on a line, `x` and `y` are the left end of its baseline.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TextRun {
+    pub x: Real,
+    pub y: Real,
+    pub advance: Real,
+    pub content: String,
+    pub bound: BoundingBox,
+    pub decorations: Vec<BoundingBox>,
+}
+
+/// `Text` settings with the spans of the text and of the word applied.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SpanSettings<'a> {
+    pub font_name: &'a str,
+    pub font_size: Real,
+    pub color: Option<Color>,
+    pub weight: FontWeight,
+    pub style: FontStyle,
+    pub synthetic_bold: bool,
+    pub synthetic_oblique: bool,
+    pub decoration: TextDecoration,
+    pub letter_spacing: Real,
+    pub line_height: Real,
+}
+
+impl<'a> SpanSettings<'a> {
+    pub(crate) fn new(fonts: &'a FontFamilies, text: &'a Text, spans: &[&'a Span]) -> Self {
+        let mut settings = SpanSettings {
+            font_name: text.font_name.as_str(),
+            font_size: text.font_size.val(),
+            color: None,
+            weight: FontWeight::Normal,
+            style: FontStyle::Normal,
+            synthetic_bold: false,
+            synthetic_oblique: false,
+            decoration: TextDecoration::default(),
+            letter_spacing: 0.0,
+            line_height: 1.0,
+        };
+
+        for span in spans {
+            if let Some(ref font_name) = span.font_name {
+                settings.font_name = font_name.as_str();
+            }
+            settings.font_size = span.font_size.unwrap_or(settings.font_size);
+            settings.color = span.color.or(settings.color);
+            settings.weight = span.weight.unwrap_or(settings.weight);
+            settings.style = span.style.unwrap_or(settings.style);
+            settings.decoration = span.decoration.unwrap_or(settings.decoration);
+            settings.letter_spacing = span.letter_spacing.unwrap_or(settings.letter_spacing);
+            settings.line_height = span.line_height.unwrap_or(settings.line_height);
+        }
+
+        let resolved = fonts.resolve(settings.font_name, settings.weight, settings.style);
+        settings.font_name = resolved.font_name;
+        settings.synthetic_bold = resolved.synthetic_bold;
+        settings.synthetic_oblique = resolved.synthetic_oblique;
+        settings
+    }
+
+    pub(crate) fn measure<'m>(&self, measurer: &'m dyn TextMeasurer) -> Measure<'m>
+    where
+        'a: 'm,
+    {
+        Measure::new(measurer, self.font_name, self.font_size).with_letter_spacing(self.letter_spacing)
+    }
+
+    pub(crate) fn embolden_offset(&self) -> Real {
+        if self.synthetic_bold {
+            self.font_size * EMBOLDEN_RATIO
+        } else {
+            0.0
+        }
+    }
+
+    /// Horizontal shift of the glyph tops by the synthetic oblique.
+    fn oblique_offset(&self, y: Real, bound: &BoundingBox) -> Real {
+        if self.synthetic_oblique {
+            OBLIQUE_SKEW * (y - bound.min_y).max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    fn decorations(&self, metrics: &FontMetrics, (x, y): (Real, Real), advance: Real) -> Vec<BoundingBox> {
+        let thickness = ((metrics.ascender - metrics.descender) * DECORATION_THICKNESS_RATIO).max(1.0);
+        let width = advance + self.embolden_offset();
+        let line = |center_y: Real| BoundingBox {
+            min_x: x,
+            min_y: center_y - thickness / 2.0,
+            max_x: x + width,
+            max_y: center_y + thickness / 2.0,
+        };
+
+        let mut decorations = Vec::new();
+        if self.decoration.underline {
+            decorations.push(line(y - metrics.descender * UNDERLINE_OFFSET_RATIO));
+        }
+        if self.decoration.overline {
+            decorations.push(line(y - metrics.ascender));
+        }
+        if self.decoration.line_through {
+            decorations.push(line(y - metrics.ascender * LINE_THROUGH_OFFSET_RATIO));
+        }
+        decorations
+    }
+
+    fn run(&self, metrics: &FontMetrics, (x, y): (Real, Real), advance: Real, content: String, bound: BoundingBox) -> TextRun {
+        let decorations = self.decorations(metrics, (x, y), advance);
+        let mut bound = bound;
+        bound.max_x += self.embolden_offset() + self.oblique_offset(y, &bound);
+        for decoration in decorations.iter() {
+            bound = bound.union(decoration);
+        }
+
+        TextRun {
+            x,
+            y,
+            advance,
+            content,
+            bound,
+            decorations,
+        }
+    }
+}
+
+/// Places a single shaped word at the `text` position with the `text` alignment.
+pub(crate) fn word_run(measurer: &dyn TextMeasurer, text: &Text, settings: &SpanSettings, content: &str) -> TextRun {
+    let measure = settings.measure(measurer);
+    let aligned = measure.aligned((text.x.val(), text.y.val()), content, &text.align.0, &text.align.1);
+    settings.run(&measure.metrics(), aligned.origin, aligned.advance, content.to_string(), aligned.bound)
+}
+
+/// Width of `bound` available to a word at the `text` position with the `text` alignment.
+pub(crate) fn available_width(text: &Text, bound: BoundingBox) -> Real {
+    let x = text.x.val();
+    let width = match text.align.0 {
+        AlignHor::Left => bound.max_x - x,
+        AlignHor::Right => x - bound.min_x,
+        AlignHor::Center => 2.0 * (x - bound.min_x).min(bound.max_x - x),
+    };
+    width.max(0.0)
+}
+
+/// Byte end and width of the longest prefix not wider than `max_width`.
+fn prefix(positions: &[GlyphPosition], content: &str, max_width: Real) -> (usize, Real) {
+    let mut prefix = (0, 0.0);
+    for (idx, glyph) in positions.iter().enumerate() {
+        if glyph.max_x > max_width {
+            break;
+        }
+        let end = positions.get(idx + 1).map_or(content.len(), |next| next.byte_index);
+        prefix = (end, glyph.max_x);
+    }
+    prefix
+}
+
+/// Byte start of the longest suffix not wider than `max_width`.
+fn suffix(positions: &[GlyphPosition], content: &str, max_width: Real) -> usize {
+    let total_width = positions.last().map_or(0.0, |glyph| glyph.max_x);
+    let mut start = content.len();
+    for glyph in positions.iter().rev() {
+        if total_width - glyph.min_x > max_width {
+            break;
+        }
+        start = glyph.byte_index;
+    }
+    start
+}
+
+/// Shortens the logical `content` of a word by `overflow` to `max_width` and shapes it.
+/// Shaping after fitting keeps the logical start of a right-to-left word, not its left end.
+pub(crate) fn fit_word(measure: &Measure, content: &str, max_width: Real, overflow: TextOverflow) -> String {
+    shaping::shape(&fit(measure, content, max_width, overflow)).into_owned()
+}
+
+/// Content shortened by `overflow` to `max_width`, empty when even the ellipsis is wider.
+pub(crate) fn fit(measure: &Measure, content: &str, max_width: Real, overflow: TextOverflow) -> String {
+    if measure.advance(content) <= max_width {
+        return content.to_string();
+    }
+
+    let positions = measure.glyph_positions(0.0, content, &AlignHor::Left);
+    let budget = max_width - measure.advance(ELLIPSIS);
+    match overflow {
+        TextOverflow::Clip => {
+            let (end, _) = prefix(&positions, content, max_width);
+            content[..end].to_string()
+        },
+        TextOverflow::Ellipsis | TextOverflow::EllipsisMiddle if budget < 0.0 => String::new(),
+        TextOverflow::Ellipsis => {
+            let (end, _) = prefix(&positions, content, budget);
+            format!("{}{}", content[..end].trim_end(), ELLIPSIS)
+        },
+        TextOverflow::EllipsisMiddle => {
+            let (end, head_width) = prefix(&positions, content, budget / 2.0);
+            let start = suffix(&positions, content, budget - head_width).max(end);
+            format!("{}{}{}", &content[..end], ELLIPSIS, &content[start..])
+        },
+    }
+}
+
+pub(crate) struct FlowWord<'a> {
+    pub content: &'a str,
+    pub settings: SpanSettings<'a>,
+}
+
+struct Piece<'a> {
+    word: usize,
+    content: &'a str,
+    advance: Real,
+    metrics: FontMetrics,
+}
+
+struct Line<'a> {
+    pieces: Vec<Piece<'a>>,
+    wrapped: bool,
+    width: Real,
+    trimmed_width: Real,
+    ascender: Real,
+    descender: Real,
+    line_height: Real,
+}
+
+impl<'a> Line<'a> {
+    fn new(metrics: FontMetrics, wrapped: bool) -> Self {
+        Line {
+            pieces: Vec::new(),
+            wrapped,
+            width: 0.0,
+            trimmed_width: 0.0,
+            ascender: metrics.ascender,
+            descender: metrics.descender,
+            line_height: metrics.line_height,
+        }
+    }
+
+    fn push(&mut self, piece: Piece<'a>, space: bool) {
+        self.width += piece.advance;
+        if !space {
+            self.trimmed_width = self.width;
+        }
+        self.ascender = self.ascender.max(piece.metrics.ascender);
+        self.descender = self.descender.min(piece.metrics.descender);
+        self.line_height = self.line_height.max(piece.metrics.line_height);
+        self.pieces.push(piece);
+    }
+}
+
+fn line_metrics(settings: &SpanSettings, measure: &Measure) -> FontMetrics {
+    let metrics = measure.metrics();
+    FontMetrics {
+        line_height: metrics.line_height * settings.line_height,
+        ..metrics
+    }
+}
+
+fn tokens(content: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev_space = None;
+    for (idx, ch) in content.char_indices() {
+        if ch == '\n' {
+            if start < idx {
+                tokens.push(&content[start..idx]);
+            }
+            tokens.push(&content[idx..idx + 1]);
+            start = idx + 1;
+            prev_space = None;
+            continue;
+        }
+
+        let space = ch.is_whitespace();
+        if prev_space.map_or(false, |prev_space| prev_space != space) {
+            tokens.push(&content[start..idx]);
+            start = idx;
+        }
+        prev_space = Some(space);
+    }
+    if start < content.len() {
+        tokens.push(&content[start..]);
+    }
+    tokens
+}
+
+/// Places the words one after another starting at the `text` position,
+/// returns the runs of every word and their combined bound.
+pub(crate) fn flow(measurer: &dyn TextMeasurer,
+                   text: &Text,
+                   base: &SpanSettings,
+                   words: &[FlowWord],
+                   max_width: Real) -> (Vec<Vec<TextRun>>, BoundingBox)
+{
+    let base_metrics = line_metrics(base, &base.measure(measurer));
+    let mut lines = vec![Line::new(base_metrics, false)];
+    let mut word_metrics = Vec::new();
+
+    for (word, flow_word) in words.iter().enumerate() {
+        let measure = flow_word.settings.measure(measurer);
+        let metrics = line_metrics(&flow_word.settings, &measure);
+        word_metrics.push(metrics);
+
+        for token in tokens(flow_word.content) {
+            if token == "\n" {
+                lines.push(Line::new(base_metrics, false));
+                continue;
+            }
+
+            let space = token.chars().all(char::is_whitespace);
+            let advance = measure.advance(token);
+            let wrap = {
+                let line = &lines[lines.len() - 1];
+                !space && !line.pieces.is_empty() && line.width + advance > max_width
+            };
+            if wrap {
+                lines.push(Line::new(base_metrics, true));
+            }
+
+            let line = lines.last_mut().expect("Lines must not be empty");
+            if space && line.wrapped && line.pieces.is_empty() {
+                continue;
+            }
+            line.push(Piece { word, content: token, advance, metrics }, space);
+        }
+    }
+
+    let (x, y) = (text.x.val(), text.y.val());
+    let last = &lines[lines.len() - 1];
+    let total_height = lines[..lines.len() - 1].iter().map(|line| line.line_height).sum::<Real>()
+        + last.ascender - last.descender;
+    let first_ascender = lines[0].ascender;
+    let top = match text.align.1 {
+        AlignVer::Top => y,
+        AlignVer::Middle => y - total_height / 2.0,
+        AlignVer::Bottom => y - total_height,
+        AlignVer::Baseline => y - first_ascender,
+    };
+
+    let mut pieces: Vec<Vec<(Real, Real, Real, String)>> = words.iter().map(|_| Vec::new()).collect();
+    let mut baseline = top + first_ascender;
+    for line in lines.iter() {
+        let mut pen_x = match text.align.0 {
+            AlignHor::Left => x,
+            AlignHor::Center => x - line.trimmed_width / 2.0,
+            AlignHor::Right => x - line.trimmed_width,
+        };
+
+        // The pieces of a word on a line are one run, drawn in the bidi order of the line
+        let mut line_runs: Vec<(usize, Real, String)> = Vec::new();
+        for piece in line.pieces.iter() {
+            match line_runs.last_mut() {
+                Some(last) if last.0 == piece.word => {
+                    last.1 += piece.advance;
+                    last.2.push_str(piece.content);
+                },
+                _ => line_runs.push((piece.word, piece.advance, piece.content.to_string())),
+            }
+        }
+        let contents: Vec<&str> = line_runs.iter().map(|run| run.2.as_str()).collect();
+        for idx in shaping::visual_order(&contents) {
+            let (word, advance, ref content) = line_runs[idx];
+            pieces[word].push((pen_x, baseline, advance, content.clone()));
+            pen_x += advance;
+        }
+        baseline += line.line_height;
+    }
+
+    let mut bound: Option<BoundingBox> = None;
+    let runs = pieces.into_iter().enumerate().map(|(word, word_pieces)| {
+        let settings = &words[word].settings;
+        let metrics = &word_metrics[word];
+        word_pieces.into_iter().map(|(run_x, run_y, advance, content)| {
+            let run_bound = BoundingBox {
+                min_x: run_x,
+                min_y: run_y - metrics.ascender,
+                max_x: run_x + advance,
+                max_y: run_y - metrics.descender,
+            };
+            let run = settings.run(metrics, (run_x, run_y), advance, content, run_bound);
+            bound = Some(bound.map_or(run.bound, |bound| bound.union(&run.bound)));
+            run
+        }).collect()
+    }).collect();
+
+    (runs, bound.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{FONT, measurer};
+
+    // Advances of Roboto at 20 px: "Hello world" 78, "abcdefgh" 65, the ellipsis 10
+    // and 4 for every Hebrew letter, which Roboto draws as the missing glyph
+    const SIZE: Real = 20.0;
+
+    #[test]
+    fn ellipsis_replaces_the_end_that_does_not_fit() {
+        let measurer = measurer();
+        let measure = Measure::new(&measurer, FONT, SIZE);
+        assert_eq!(fit(&measure, "Hello world", 80.0, TextOverflow::Ellipsis), "Hello world");
+        assert_eq!(fit(&measure, "Hello world", 40.0, TextOverflow::Ellipsis), "Hell\u{2026}");
+        assert_eq!(fit(&measure, "Hello world", 40.0, TextOverflow::Clip), "Hello ");
+    }
+
+    #[test]
+    fn middle_ellipsis_keeps_both_ends() {
+        let measurer = measurer();
+        let measure = Measure::new(&measurer, FONT, SIZE);
+        assert_eq!(fit(&measure, "abcdefgh", 40.0, TextOverflow::EllipsisMiddle), "a\u{2026}gh");
+        assert_eq!(fit(&measure, "abcdefgh", 65.0, TextOverflow::EllipsisMiddle), "abcdefgh");
+    }
+
+    #[test]
+    fn nothing_is_left_when_the_ellipsis_does_not_fit() {
+        let measurer = measurer();
+        let measure = Measure::new(&measurer, FONT, SIZE);
+        assert_eq!(fit(&measure, "Hello world", 8.0, TextOverflow::Ellipsis), "");
+        assert_eq!(fit(&measure, "Hello world", 8.0, TextOverflow::EllipsisMiddle), "");
+        assert_eq!(fit(&measure, "Hello world", 8.0, TextOverflow::Clip), "");
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn right_to_left_words_lose_their_logical_end() {
+        let measurer = measurer();
+        let measure = Measure::new(&measurer, FONT, SIZE);
+        let fitted = fit_word(&measure, "\u{05D0}\u{05D1}\u{05D2}\u{05D3}\u{05D4}", 18.0, TextOverflow::Ellipsis);
+        assert_eq!(fitted, "\u{2026}\u{05D1}\u{05D0}");
+    }
+}
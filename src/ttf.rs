@@ -0,0 +1,34 @@
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|bytes| u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+}
+
+pub(crate) fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from(read_u16(data, offset)?) << 16 | u32::from(read_u16(data, offset + 2)?))
+}
+
+/// Offset of the first face in a font file, which may be a collection.
+pub(crate) fn font_offset(data: &[u8]) -> Option<usize> {
+    if data.get(0..4)? == b"ttcf" {
+        read_u32(data, 12).map(|offset| offset as usize)
+    } else {
+        Some(0)
+    }
+}
+
+/// Offset of the table with `tag` in the face at `font_offset`.
+pub(crate) fn table(data: &[u8], font_offset: usize, tag: &[u8; 4]) -> Option<usize> {
+    table_range(data, font_offset, tag).map(|(offset, _)| offset)
+}
+
+/// Offset and length of the table with `tag` in the face at `font_offset`.
+pub(crate) fn table_range(data: &[u8], font_offset: usize, tag: &[u8; 4]) -> Option<(usize, usize)> {
+    let num_tables = read_u16(data, font_offset + 4)? as usize;
+    let record = (0..num_tables)
+        .map(|idx| font_offset + 12 + idx * 16)
+        .find(|&record| data.get(record..record + 4) == Some(&tag[..]))?;
+    Some((read_u32(data, record + 8)? as usize, read_u32(data, record + 12)? as usize))
+}
@@ -0,0 +1,53 @@
+//! Helpers shared by the unit tests of the crate and its integration tests, which include
+//! this module with a `FontFileMeasurer` in scope of its parent.
+
+#![allow(dead_code, unused_macros)]
+
+use super::FontFileMeasurer;
+
+pub const FONT: &str = "Roboto";
+pub const ROBOTO: &str = "resources/Roboto-Regular.ttf";
+
+pub fn measurer() -> FontFileMeasurer {
+    let mut measurer = FontFileMeasurer::new();
+    measurer.load_font(FONT, ROBOTO).expect("Roboto must be loadable");
+    measurer
+}
+
+pub fn assert_close(actual: f32, expected: f32, what: &str) {
+    assert!((actual - expected).abs() < 1e-3, "{}: {} != {}", what, actual, expected);
+}
+
+/// Declares the component `$name` without messages or properties that shows the `egml!`
+/// view, with `$name::comp()` building and resolving it.
+macro_rules! scene {
+    ($name:ident, { $($view:tt)* }) => {
+        #[derive(Debug, Default)]
+        struct $name;
+
+        impl ::exgui::Component for $name {
+            type Message = ();
+            type Properties = ();
+
+            fn create(_props: &Self::Properties) -> Self {
+                $name
+            }
+
+            fn update(&mut self, _msg: Self::Message) -> ::exgui::ChangeView {
+                ::exgui::ChangeView::None
+            }
+
+            fn view(&self) -> ::exgui::Node<Self> {
+                egml! { $($view)* }
+            }
+        }
+
+        impl $name {
+            fn comp() -> ::exgui::Comp {
+                let mut comp = ::exgui::Comp::new::<$name>(());
+                comp.resolve(None);
+                comp
+            }
+        }
+    };
+}
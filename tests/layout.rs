@@ -0,0 +1,210 @@
+//! Layout checks of the recalc pass with text measured by `FontFileMeasurer` over Roboto.
+
+use exgui_renderer_nanovg::{
+    NanovgRenderer, FontFileMeasurer, LayoutNode, LayoutValue, BoundingBox, NodeStyle, Anchor, AnchorPoint,
+    PathUnits,
+};
+use exgui::{egml, Color, Transform, Shape, Drawable, AlignHor::*, AlignVer::*, PathCommand::*};
+
+#[macro_use]
+mod common;
+
+use common::{measurer, assert_close};
+
+const ROTATION: f32 = 0.5;
+
+fn renderer() -> NanovgRenderer {
+    NanovgRenderer::default().with_width(400.0).with_height(300.0)
+}
+
+fn find<'a>(node: &'a LayoutNode, kind: &str) -> Option<&'a LayoutNode> {
+    if node.kind == kind {
+        return Some(node);
+    }
+    node.children.iter().filter_map(|child| find(child, kind)).next()
+}
+
+/// Dotted id of `prefix` followed by `rest`, as the dumps print paths.
+fn id(prefix: &[usize], rest: &[usize]) -> String {
+    prefix.iter().chain(rest).map(|idx| idx.to_string()).collect::<Vec<_>>().join(".")
+}
+
+scene!(RotatedText, {
+    <group>
+        <text x = 100, y = 50, font_name = "Roboto", font_size = 24, align = (Center, Middle),
+                fill = Color::Black, transform = Transform::new().with_rotation(ROTATION), >
+            { "Align" }
+        </text>
+    </group>
+});
+
+/// Unrotated, the centered "Align" spans (78, 38)..(122, 62): Roboto's 42 px advance, its glyph
+/// quads from -1 to 43 px and its 24 px line height. Rotating the corners of that box by
+/// 0.5 rad about the origin gives the expected bound.
+#[test]
+fn rotated_text_bound_encloses_the_rotated_glyph_box() {
+    let measurer = measurer();
+    let mut comp = RotatedText::comp();
+
+    let layout = renderer().layout_with(&measurer, &mut comp);
+    let word = find(&layout, "Word").expect("The text must have a word");
+    let bound = word.bound.expect("The word must have a bound");
+
+    assert_close(bound.min_x, 38.7271, "min_x");
+    assert_close(bound.min_y, 70.7433, "min_y");
+    assert_close(bound.max_x, 88.8469, "max_x");
+    assert_close(bound.max_y, 112.9, "max_y");
+}
+
+scene!(Scene, {
+    <group>
+        <rect x = 10, y = 20, width = 200, height = 100, fill = Color::White, >
+            <text x = 20, y = 40, font_name = "Roboto", font_size = 20,
+                    align = (Left, Top), fill = Color::Black, >
+                { "Hello" }
+            </text>
+        </rect>
+        <circle cx = 150, cy = 200, r = 20, fill = Color::Blue, />
+    </group>
+});
+
+#[test]
+fn layout_dumps_match_the_snapshots() {
+    let measurer = measurer();
+    let mut comp = Scene::comp();
+
+    let layout = renderer().layout_with(&measurer, &mut comp);
+    let group = find(&layout, "Group").expect("The scene must have a group");
+    let p = &group.path;
+
+    let expected_text = format!(
+        "Group [{}] bound=(0.00, 0.00, 400.00, 300.00)\n\
+         \x20 Rect [{}] bound=(10.00, 20.00, 210.00, 120.00) x=10.00 y=20.00 width=200.00 height=100.00\n\
+         \x20   Text [{}] bound=(20.00, 40.00, 56.00, 60.00) x=20.00 y=40.00 font_size=20.00\n\
+         \x20     Word [{}] bound=(20.00, 40.00, 56.00, 60.00) content=\"Hello\"\n\
+         \x20 Circle [{}] bound=(130.00, 180.00, 170.00, 220.00) cx=150.00 cy=200.00 r=20.00\n",
+        id(p, &[]), id(p, &[0]), id(p, &[0, 0]), id(p, &[0, 0, 0]), id(p, &[1]),
+    );
+    assert_eq!(group.to_text(), expected_text);
+
+    let json_path = |rest: &[usize]| {
+        p.iter().chain(rest).map(|idx| idx.to_string()).collect::<Vec<_>>().join(", ")
+    };
+    let expected_json = format!(
+        r#"{{
+  "kind": "Text",
+  "path": [{}],
+  "bound": {{"min_x": 20.00, "min_y": 40.00, "max_x": 56.00, "max_y": 60.00}},
+  "props": {{"x": 20.00, "y": 40.00, "font_size": 20.00}},
+  "transform": null,
+  "children": [
+    {{
+      "kind": "Word",
+      "path": [{}],
+      "bound": {{"min_x": 20.00, "min_y": 40.00, "max_x": 56.00, "max_y": 60.00}},
+      "props": {{"content": "Hello"}},
+      "transform": null,
+      "children": []
+    }}
+  ]
+}}
+"#,
+        json_path(&[0, 0]), json_path(&[0, 0, 0]),
+    );
+    let text = find(group, "Text").expect("The scene must have a text");
+    assert_eq!(text.to_json(), expected_json);
+}
+
+scene!(Anchored, {
+    <group>
+        <rect fill = Color::White, >
+            <rect x = 10, y = 20, width = 50, height = 30, fill = Color::Blue, />
+        </rect>
+        <text x = 5, y = 5, font_name = "Roboto", font_size = 20,
+                align = (Left, Top), fill = Color::Black, >
+            { "Hello" }
+        </text>
+    </group>
+});
+
+fn assert_bound(bound: Option<BoundingBox>, (min_x, min_y, max_x, max_y): (f32, f32, f32, f32), what: &str) {
+    let bound = bound.unwrap_or_else(|| panic!("{} must have a bound", what));
+    assert_close(bound.min_x, min_x, what);
+    assert_close(bound.min_y, min_y, what);
+    assert_close(bound.max_x, max_x, what);
+    assert_close(bound.max_y, max_y, what);
+}
+
+#[test]
+fn anchored_nodes_move_their_content_and_keep_their_own_properties() {
+    let measurer = measurer();
+    let mut renderer = renderer();
+    let mut comp = Anchored::comp();
+    let p = NanovgRenderer::find_path(&comp, |shape| matches!(shape, Shape::Group(_)))
+        .expect("The scene must have a group");
+    let child = |idx: usize| p.iter().cloned().chain(Some(idx)).collect::<Vec<_>>();
+    renderer.set_style(child(0), NodeStyle {
+        anchor: Some(Anchor::new(AnchorPoint::BottomRight)),
+        ..Default::default()
+    });
+    renderer.set_style(child(1), NodeStyle {
+        anchor: Some(Anchor::new(AnchorPoint::Center)),
+        ..Default::default()
+    });
+
+    // Laying out twice checks that the anchors do not accumulate in the tree
+    for _ in 0..2 {
+        let layout = renderer.layout_with(&measurer, &mut comp);
+        let group = find(&layout, "Group").expect("The scene must have a group");
+        let (outer, text) = (&group.children[0], &group.children[1]);
+
+        assert_bound(outer.bound, (350.0, 270.0, 400.0, 300.0), "The auto sized rect");
+        assert_bound(outer.children[0].bound, (350.0, 270.0, 400.0, 300.0), "The child of the anchored rect");
+        assert_eq!(outer.children[0].props[0], ("x", LayoutValue::Number(10.0)));
+        assert_eq!(outer.children[0].props[1], ("y", LayoutValue::Number(20.0)));
+
+        assert_eq!(text.props[0], ("x", LayoutValue::Number(5.0)));
+        assert_eq!(text.props[1], ("y", LayoutValue::Number(5.0)));
+        assert_bound(text.children[0].bound, (182.0, 140.0, 218.0, 160.0), "The word of the anchored text");
+    }
+
+    let text = NanovgRenderer::find_path(&comp, |shape| matches!(shape, Shape::Text(_)))
+        .and_then(|path| {
+            let mut node: &dyn Drawable = &comp;
+            for &idx in path.iter() {
+                node = node.childs()?.into_iter().nth(idx)?;
+            }
+            match node.shape() {
+                Some(Shape::Text(text)) => Some(text.clone()),
+                _ => None,
+            }
+        })
+        .expect("The scene must have a text");
+    assert!(matches!((&text.align.0, &text.align.1), (Left, Top)), "The anchor must not change the text alignment");
+}
+
+scene!(UnitPath, {
+    <rect x = 0, y = 0, width = 200, height = 100, fill = Color::White, >
+        <path cmd = vec![Move([0.0, 50.0]), Line([100.0, 50.0])], stroke = (Color::Red, 2), />
+    </rect>
+});
+
+#[test]
+fn path_units_map_the_points_and_keep_the_stroke_width() {
+    let measurer = measurer();
+    let mut renderer = renderer();
+    let mut comp = UnitPath::comp();
+    let path = NanovgRenderer::find_path(&comp, |shape| matches!(shape, Shape::Path(_)))
+        .expect("The scene must have a path");
+    renderer.set_style(path, NodeStyle {
+        path_units: Some(PathUnits::Pct),
+        ..Default::default()
+    });
+
+    let layout = renderer.layout_with(&measurer, &mut comp);
+    let bound = find(&layout, "Path").and_then(|path| path.bound).expect("The path must have a bound");
+    // (0, 50)..(100, 50) in percent of 200 by 100 is (0, 50)..(200, 50), stroked 2 wide
+    assert_close(bound.min_y, 49.0, "min_y");
+    assert_close(bound.max_y, 51.0, "max_y");
+    assert!(bound.max_x >= 200.0, "The line must span the rect");
+}
@@ -0,0 +1,66 @@
+//! Invalidation of the recalc cache by style changes.
+
+use exgui_renderer_nanovg::{NanovgRenderer, FontFileMeasurer, NodeStyle, Anchor, AnchorPoint, Visibility};
+use exgui::{egml, Color, Shape, AlignHor::*, AlignVer::*};
+
+#[macro_use]
+mod common;
+
+use common::measurer;
+
+scene!(Siblings, {
+    <group>
+        <rect x = 10, y = 10, width = 50, height = 20, fill = Color::Red, />
+        <rect x = 100, y = 10, width = 50, height = 20, fill = Color::Blue, />
+        <text x = 10, y = 100, font_name = "Roboto", font_size = 20, align = (Left, Top), fill = Color::Black, >
+            { "Hello" }
+        </text>
+    </group>
+});
+
+fn cached_renderer() -> NanovgRenderer {
+    NanovgRenderer::default().with_width(400.0).with_height(300.0).with_recalc_cache()
+}
+
+#[test]
+fn drawn_styles_keep_the_recalc_cache() {
+    let measurer = measurer();
+    let mut renderer = cached_renderer();
+    let mut comp = Siblings::comp();
+    let rect = NanovgRenderer::find_path(&comp, |shape| matches!(shape, Shape::Rect(_)))
+        .expect("The scene must have a rect");
+
+    renderer.recalc_with(&measurer, &mut comp);
+    renderer.set_style(rect.clone(), NodeStyle {
+        z_index: Some(2),
+        opacity: Some(0.5),
+        overlay: Some(true),
+        ..Default::default()
+    });
+    renderer.set_visibility(rect, Visibility::Hidden);
+    let stats = renderer.recalc_with(&measurer, &mut comp);
+    assert_eq!(stats.recalculated_nodes, 0);
+    assert_eq!(stats.text_measurements, 0);
+}
+
+#[test]
+fn a_layout_style_recalculates_the_node_and_its_ancestors_only() {
+    let measurer = measurer();
+    let mut renderer = cached_renderer();
+    let mut comp = Siblings::comp();
+    let rect = NanovgRenderer::find_path(&comp, |shape| matches!(shape, Shape::Rect(_)))
+        .expect("The scene must have a rect");
+
+    renderer.recalc_with(&measurer, &mut comp);
+    renderer.set_style(rect.clone(), NodeStyle {
+        anchor: Some(Anchor::new(AnchorPoint::Center)),
+        ..Default::default()
+    });
+    let stats = renderer.recalc_with(&measurer, &mut comp);
+    assert_eq!(stats.recalculated_nodes, rect.len() + 1, "The rect and its ancestors are recalculated");
+    assert_eq!(stats.text_measurements, 0, "The text is reused");
+
+    renderer.remove_style(&rect);
+    let stats = renderer.recalc_with(&measurer, &mut comp);
+    assert_eq!(stats.recalculated_nodes, rect.len() + 1);
+}